@@ -0,0 +1,405 @@
+//! Cellular automata on rectangular grids, shared by the octopus,
+//! trench-map and sea-cucumber days. A `Rule` advances a `Grid` one
+//! generation; an `Automaton` drives it.
+
+use std::collections::VecDeque;
+
+/// What lies past the edges of a grid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Boundary {
+    /// Nothing: off-grid cells have no value.
+    Bounded,
+    /// The grid wraps around at every edge.
+    Toroidal,
+    /// An endless plane of cells equal to the grid's `background`.
+    Infinite,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Grid<T> {
+    pub cells: Vec<Vec<T>>,
+    pub boundary: Boundary,
+    /// Value of every cell off the grid when the boundary is infinite.
+    pub background: T,
+}
+
+// The eight neighbouring offsets.
+const AROUND: [(i64, i64); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+impl<T: Clone + PartialEq> Grid<T> {
+    pub fn new(cells: Vec<Vec<T>>, boundary: Boundary, background: T) -> Self {
+        Grid {
+            cells,
+            boundary,
+            background,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    /// The on-grid position of (i, j) after wrapping, if it has one.
+    pub fn locate(&self, i: i64, j: i64) -> Option<(usize, usize)> {
+        let (n, m) = (self.rows() as i64, self.cols() as i64);
+        match self.boundary {
+            Boundary::Toroidal => Some((i.rem_euclid(n) as usize, j.rem_euclid(m) as usize)),
+            _ if (0..n).contains(&i) && (0..m).contains(&j) => Some((i as usize, j as usize)),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, i: i64, j: i64) -> Option<&T> {
+        match self.locate(i, j) {
+            Some((x, y)) => Some(&self.cells[x][y]),
+            None if self.boundary == Boundary::Infinite => Some(&self.background),
+            None => None,
+        }
+    }
+
+    /// On-grid positions of the eight neighbours of (i, j).
+    pub fn neighbors(&self, i: usize, j: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        AROUND
+            .iter()
+            .filter_map(move |&(di, dj)| self.locate(i as i64 + di, j as i64 + dj))
+    }
+
+    /// Surrounds the grid with `k` rings of background cells.
+    pub fn pad(&mut self, k: usize) {
+        let m = self.cols() + 2 * k;
+        let blank = vec![self.background.clone(); m];
+        let mut cells = vec![blank.clone(); k];
+        for row in self.cells.drain(..) {
+            let mut r = vec![self.background.clone(); k];
+            r.extend(row);
+            r.extend(vec![self.background.clone(); k]);
+            cells.push(r);
+        }
+        cells.extend(vec![blank; k]);
+        self.cells = cells;
+    }
+
+    /// Number of on-grid cells matching `f`.
+    pub fn count(&self, f: impl Fn(&T) -> bool) -> usize {
+        self.cells.iter().flatten().filter(|c| f(c)).count()
+    }
+
+    pub fn render(&self, f: impl Fn(&T) -> char) -> String {
+        self.cells
+            .iter()
+            .map(|r| r.iter().map(&f).collect::<String>() + "\n")
+            .collect()
+    }
+}
+
+/// How a grid moves from one generation to the next.
+pub trait Rule {
+    type Cell: Clone + PartialEq;
+
+    /// Advances `grid` one generation and returns how many cells were
+    /// active in it: flashed, moved or changed, as the rule defines.
+    fn step(&self, grid: &mut Grid<Self::Cell>) -> usize;
+}
+
+pub struct Automaton<R: Rule> {
+    pub grid: Grid<R::Cell>,
+    pub rule: R,
+    /// Generations run so far.
+    pub generation: usize,
+}
+
+impl<R: Rule> Automaton<R> {
+    pub fn new(grid: Grid<R::Cell>, rule: R) -> Self {
+        Automaton {
+            grid,
+            rule,
+            generation: 0,
+        }
+    }
+
+    pub fn step(&mut self) -> usize {
+        self.generation += 1;
+        self.rule.step(&mut self.grid)
+    }
+
+    /// Runs `n` generations and returns their total activity.
+    pub fn run(&mut self, n: usize) -> usize {
+        (0..n).map(|_| self.step()).sum()
+    }
+
+    /// Steps until `done` holds for the grid and the generation's activity,
+    /// giving up after `limit` generations. Returns the generation number.
+    pub fn run_until(
+        &mut self,
+        limit: usize,
+        mut done: impl FnMut(&Grid<R::Cell>, usize) -> bool,
+    ) -> Option<usize> {
+        for _ in 0..limit {
+            let active = self.step();
+            if done(&self.grid, active) {
+                return Some(self.generation);
+            }
+        }
+        None
+    }
+
+    /// Steps until a generation has no activity, giving up after `limit`
+    /// generations. Returns that generation's number. This is the first
+    /// generation to leave the grid unchanged only for rules whose activity
+    /// counts changes, such as `Lookup` and `Herds`; a `Cascade` grid
+    /// changes every generation whether or not anything flashes.
+    pub fn run_until_stable(&mut self, limit: usize) -> Option<usize> {
+        self.run_until(limit, |_, active| active == 0)
+    }
+}
+
+/// Every cell gains 1; cells above `threshold` flash once per generation,
+/// dropping to 0 and giving 1 to each neighbour, which may flash in turn.
+/// Activity is the number of flashes.
+pub struct Cascade {
+    pub threshold: u8,
+}
+
+impl Rule for Cascade {
+    type Cell = u8;
+
+    fn step(&self, grid: &mut Grid<u8>) -> usize {
+        let mut flashed = vec![vec![false; grid.cols()]; grid.rows()];
+        let mut queue = VecDeque::new();
+        for i in 0..grid.rows() {
+            for j in 0..grid.cols() {
+                queue.push_back((i, j));
+            }
+        }
+        let mut flashes = 0;
+        while let Some((i, j)) = queue.pop_front() {
+            if flashed[i][j] {
+                continue;
+            }
+            grid.cells[i][j] += 1;
+            if grid.cells[i][j] > self.threshold {
+                grid.cells[i][j] = 0;
+                flashed[i][j] = true;
+                flashes += 1;
+                queue.extend(grid.neighbors(i, j));
+            }
+        }
+        flashes
+    }
+}
+
+/// Each cell becomes `table[k]`, where k reads its 3x3 neighbourhood row by
+/// row as a binary number (off cells past a bounded edge). An infinite
+/// grid grows by one ring per generation and its background evolves too.
+/// Activity is the number of cells that changed, plus one if the
+/// background did.
+pub struct Lookup {
+    pub table: Vec<bool>,
+}
+
+impl Rule for Lookup {
+    type Cell = bool;
+
+    fn step(&self, grid: &mut Grid<bool>) -> usize {
+        if grid.boundary == Boundary::Infinite {
+            grid.pad(1);
+        }
+        let (n, m) = (grid.rows() as i64, grid.cols() as i64);
+        let window = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 0),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+        let mut changed = 0;
+        let cells: Vec<Vec<bool>> = (0..n)
+            .map(|i| {
+                (0..m)
+                    .map(|j| {
+                        let k = window.iter().fold(0, |k, (di, dj)| {
+                            2 * k + *grid.get(i + di, j + dj).unwrap_or(&false) as usize
+                        });
+                        let c = self.table[k];
+                        if c != grid.cells[i as usize][j as usize] {
+                            changed += 1;
+                        }
+                        c
+                    })
+                    .collect()
+            })
+            .collect();
+        grid.cells = cells;
+        let background = self.table[if grid.background { 511 } else { 0 }];
+        if background != grid.background {
+            grid.background = background;
+            changed += 1;
+        }
+        changed
+    }
+}
+
+/// Herds move in order, each cell of a herd stepping by its offset into
+/// an `empty` cell all at once, judged on the grid as the herd found it.
+/// Cells past a non-toroidal edge block the way. Activity is the number
+/// of moves.
+pub struct Herds<T> {
+    pub empty: T,
+    pub herds: Vec<(T, (i64, i64))>,
+}
+
+impl<T: Clone + PartialEq> Rule for Herds<T> {
+    type Cell = T;
+
+    fn step(&self, grid: &mut Grid<T>) -> usize {
+        let mut moved = 0;
+        for (kind, (di, dj)) in &self.herds {
+            let mut moves = vec![];
+            for i in 0..grid.rows() {
+                for j in 0..grid.cols() {
+                    if grid.cells[i][j] != *kind {
+                        continue;
+                    }
+                    if let Some((x, y)) = grid.locate(i as i64 + di, j as i64 + dj) {
+                        if grid.cells[x][y] == self.empty {
+                            moves.push(((i, j), (x, y)));
+                        }
+                    }
+                }
+            }
+            for &((i, j), (x, y)) in &moves {
+                grid.cells[i][j] = self.empty.clone();
+                grid.cells[x][y] = kind.clone();
+            }
+            moved += moves.len();
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(rows: &[&str]) -> Vec<Vec<u8>> {
+        rows.iter()
+            .map(|r| r.bytes().map(|b| b - b'0').collect())
+            .collect()
+    }
+
+    fn chars(rows: &[&str]) -> Vec<Vec<char>> {
+        rows.iter().map(|r| r.chars().collect()).collect()
+    }
+
+    fn cucumbers() -> Herds<char> {
+        Herds {
+            empty: '.',
+            herds: vec![('>', (0, 1)), ('v', (1, 0))],
+        }
+    }
+
+    #[test]
+    fn locate_wraps_on_torus() {
+        let mut g = Grid::new(vec![vec![0; 3]; 2], Boundary::Toroidal, 0);
+        assert_eq!(g.locate(-1, -1), Some((1, 2)));
+        assert_eq!(g.locate(2, 3), Some((0, 0)));
+        assert_eq!(g.locate(5, -4), Some((1, 2)));
+        assert_eq!(g.neighbors(0, 0).count(), 8);
+        g.boundary = Boundary::Bounded;
+        assert_eq!(g.locate(-1, 0), None);
+        assert_eq!(g.get(2, 0), None);
+        assert_eq!(g.neighbors(0, 0).count(), 3);
+    }
+
+    #[test]
+    fn cascade_flashes_spread() {
+        let grid = Grid::new(
+            digits(&["11111", "19991", "19191", "19991", "11111"]),
+            Boundary::Bounded,
+            0,
+        );
+        let mut a = Automaton::new(grid, Cascade { threshold: 9 });
+        assert_eq!(a.step(), 9);
+        assert_eq!(
+            a.grid.cells,
+            digits(&["34543", "40004", "50005", "40004", "34543"])
+        );
+        assert_eq!(a.step(), 0);
+        assert_eq!(
+            a.grid.cells,
+            digits(&["45654", "51115", "61116", "51115", "45654"])
+        );
+    }
+
+    #[test]
+    fn lookup_flips_infinite_background() {
+        // Dark neighbourhoods light up and lit ones go dark, so the whole
+        // plane blinks.
+        let table: Vec<bool> = (0..512).map(|k| k == 0).collect();
+        let grid = Grid::new(vec![vec![false]], Boundary::Infinite, false);
+        let mut a = Automaton::new(grid, Lookup { table });
+        assert_eq!(a.step(), 10);
+        assert!(a.grid.background);
+        assert_eq!((a.grid.rows(), a.grid.count(|&c| c)), (3, 9));
+        // The new ring is padded lit, so all 25 cells go dark.
+        assert_eq!(a.step(), 26);
+        assert!(!a.grid.background);
+        assert_eq!((a.grid.rows(), a.grid.count(|&c| c)), (5, 0));
+    }
+
+    #[test]
+    fn herds_move_in_order() {
+        // The east-facing herd moves first: the `>` on the right vacates the
+        // square the `v` above it moves into, while the `v` on the left
+        // still blocks the `>` behind it.
+        let grid = Grid::new(
+            chars(&["..........", ".>v....v..", ".......>..", ".........."]),
+            Boundary::Toroidal,
+            '.',
+        );
+        let mut a = Automaton::new(grid, cucumbers());
+        assert_eq!(a.step(), 3);
+        assert_eq!(
+            a.grid.cells,
+            chars(&["..........", ".>........", "..v....v>.", ".........."])
+        );
+    }
+
+    #[test]
+    fn herds_settle() {
+        let grid = Grid::new(
+            chars(&[
+                "v...>>.vv>",
+                ".vv>>.vv..",
+                ">>.>v>...v",
+                ">>v>>.>.v.",
+                "v>v.vv.v..",
+                ">.>>..v...",
+                ".vv..>.>v.",
+                "v.v..>>v.v",
+                "....v..v.>",
+            ]),
+            Boundary::Toroidal,
+            '.',
+        );
+        let mut a = Automaton::new(grid, cucumbers());
+        assert_eq!(a.run_until_stable(1000), Some(58));
+    }
+}
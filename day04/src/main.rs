@@ -1,165 +1,328 @@
+use rng::Rng;
+use std::collections::HashMap;
+use std::env;
 use std::io::{self, BufRead};
 
-fn parse_card_line(s: &str) -> [i64; 5] {
-    let mut r = [0; 5];
-    s.split_whitespace()
-        .map(|x| x.parse().unwrap())
-        .enumerate()
-        .for_each(|(i, x)| {
-            r[i] = x;
-        });
-    r
+// A square bingo card, numbers stored row-major.
+#[derive(Debug, Clone)]
+struct Card {
+    n: usize,
+    cells: Vec<i64>,
 }
 
-fn parse_input(lines: &Vec<String>) -> (Vec<i64>, Vec<[[i64; 5]; 5]>) {
+impl Card {
+    // Every row and column, plus both diagonals if enabled.
+    fn lines(&self, diagonals: bool) -> Vec<Vec<i64>> {
+        let n = self.n;
+        let at = |r: usize, c: usize| self.cells[r * n + c];
+        let mut lines = vec![];
+        for i in 0..n {
+            lines.push((0..n).map(|c| at(i, c)).collect());
+            lines.push((0..n).map(|r| at(r, i)).collect());
+        }
+        if diagonals {
+            lines.push((0..n).map(|i| at(i, i)).collect());
+            lines.push((0..n).map(|i| at(i, n - 1 - i)).collect());
+        }
+        lines
+    }
+}
+
+// Cards are separated by blank lines; their size is the number of rows.
+fn parse_input(lines: &Vec<String>) -> (Vec<i64>, Vec<Card>) {
     let nums = lines[0]
         .split(',')
         .map(|x| x.parse().unwrap())
         .collect::<Vec<i64>>();
 
-    let cards: Vec<[[i64; 5]; 5]> = lines[1..]
-        .iter()
-        .filter(|x| *x != "")
-        .map(|x| parse_card_line(x))
-        .enumerate()
-        .fold(vec![], |mut acc, (i, x)| {
-            if i % 5 == 0 {
-                acc.push([[0; 5]; 5]);
-            }
-            let n = acc.len();
-            for idx in 0..5 {
-                acc[n - 1][i % 5][idx] = x[idx];
+    let mut cards = vec![];
+    let mut rows: Vec<Vec<i64>> = vec![];
+    for line in lines[1..].iter().chain([&String::new()]) {
+        if line.trim().is_empty() {
+            if !rows.is_empty() {
+                assert!(
+                    rows.iter().all(|r| r.len() == rows.len()),
+                    "card is not square"
+                );
+                cards.push(Card {
+                    n: rows.len(),
+                    cells: rows.concat(),
+                });
+                rows.clear();
             }
-            acc
-        });
+            continue;
+        }
+        rows.push(
+            line.split_whitespace()
+                .map(|x| x.parse().unwrap())
+                .collect(),
+        );
+    }
 
     (nums, cards)
 }
 
-fn got_bingo(card: &[[i64; 5]; 5], marks: &[[bool; 5]; 5]) -> Option<i64> {
-    let mut bingo = false;
-    for r in 0..5 {
-        let mut won = true;
-        for c in 0..5 {
-            if marks[r][c] == false {
-                won = false;
-                break;
-            }
-        }
-        if won {
-            bingo = true;
-            break;
-        }
+// Marks left to complete each line of one card, plus its unmarked sum.
+struct CardState {
+    rows: Vec<usize>,
+    cols: Vec<usize>,
+    diags: [usize; 2],
+    marked: Vec<bool>,
+    unmarked: i64,
+    won: bool,
+}
 
-        won = true;
-        for c in 0..5 {
-            if marks[c][r] == false {
-                won = false;
-                break;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Win {
+    card: usize,
+    // Index into the draw list of the winning number.
+    draw: usize,
+    score: i64,
+}
+
+struct Bingo<'a> {
+    cards: &'a [Card],
+    // number -> every (card, cell) holding it
+    index: HashMap<i64, Vec<(usize, usize)>>,
+    diagonals: bool,
+}
+
+impl<'a> Bingo<'a> {
+    fn new(cards: &'a [Card], diagonals: bool) -> Self {
+        let mut index: HashMap<i64, Vec<(usize, usize)>> = HashMap::new();
+        for (k, card) in cards.iter().enumerate() {
+            for (i, x) in card.cells.iter().enumerate() {
+                index.entry(*x).or_default().push((k, i));
             }
         }
-        if won {
-            bingo = true;
-            break;
+        Bingo {
+            cards,
+            index,
+            diagonals,
         }
     }
 
-    if bingo {
-        let mut sum = 0;
-        for a in 0..5 {
-            for b in 0..5 {
-                if marks[a][b] == false {
-                    sum += card[a][b];
+    // Every card that wins, in order of winning (ties in card order), with
+    // the draw it won on and its score.
+    fn play(&self, nums: &[i64]) -> Vec<Win> {
+        let mut states = self
+            .cards
+            .iter()
+            .map(|c| CardState {
+                rows: vec![c.n; c.n],
+                cols: vec![c.n; c.n],
+                diags: [c.n; 2],
+                marked: vec![false; c.cells.len()],
+                unmarked: c.cells.iter().sum(),
+                won: false,
+            })
+            .collect::<Vec<_>>();
+        let mut wins = vec![];
+        for (draw, x) in nums.iter().enumerate() {
+            let mut winners = vec![];
+            for &(k, i) in self.index.get(x).into_iter().flatten() {
+                let n = self.cards[k].n;
+                let st = &mut states[k];
+                if st.won || st.marked[i] {
+                    continue;
+                }
+                let (r, c) = (i / n, i % n);
+                st.marked[i] = true;
+                st.unmarked -= x;
+                st.rows[r] -= 1;
+                st.cols[c] -= 1;
+                let mut done = st.rows[r] == 0 || st.cols[c] == 0;
+                if self.diagonals {
+                    if r == c {
+                        st.diags[0] -= 1;
+                        done |= st.diags[0] == 0;
+                    }
+                    if r + c == n - 1 {
+                        st.diags[1] -= 1;
+                        done |= st.diags[1] == 0;
+                    }
+                }
+                if done {
+                    winners.push(k);
                 }
             }
+            winners.sort_unstable();
+            winners.dedup();
+            for k in winners {
+                states[k].won = true;
+                wins.push(Win {
+                    card: k,
+                    draw,
+                    score: states[k].unmarked * x,
+                });
+            }
         }
-        Some(sum)
-    } else {
-        None
+        wins
     }
 }
 
-fn solve1(nums: &Vec<i64>, cards: &Vec<[[i64; 5]; 5]>) -> i64 {
-    let n = cards.len();
-    let mut marks: Vec<[[bool; 5]; 5]> = Vec::with_capacity(n);
-    for _i in 0..n {
-        marks.push([[false; 5]; 5]);
-    }
-
-    nums.iter()
-        .filter_map(|x| {
-            cards
-                .iter()
-                .enumerate()
-                .filter_map(|(i, c)| {
-                    for a in 0..5 {
-                        for b in 0..5 {
-                            if c[a][b] == *x {
-                                marks[i][a][b] = true;
-                            }
-                        }
-                    }
-
-                    got_bingo(c, &marks[i]).map(|s| s * *x)
-                })
-                .nth(0)
+// The fewest further draws from `pool` that complete a line of `card` given
+// the numbers already `drawn`, and the numbers to draw; None if no line can
+// be completed.
+fn earliest_win(
+    card: &Card,
+    drawn: &[i64],
+    pool: &[i64],
+    diagonals: bool,
+) -> Option<(usize, Vec<i64>)> {
+    card.lines(diagonals)
+        .into_iter()
+        .map(|l| {
+            l.into_iter()
+                .filter(|x| !drawn.contains(x))
+                .collect::<Vec<_>>()
         })
-        .nth(0)
-        .unwrap()
+        .filter(|missing| missing.iter().all(|x| pool.contains(x)))
+        .min_by_key(|missing| missing.len())
+        .map(|missing| (missing.len(), missing))
+}
+
+// Keeps the first `fixed` draws and shuffles the rest `trials` times. Returns
+// how often each card was (one of) the first to win.
+fn monte_carlo(
+    nums: &[i64],
+    cards: &[Card],
+    fixed: usize,
+    trials: usize,
+    seed: u64,
+    diagonals: bool,
+) -> Vec<usize> {
+    let bingo = Bingo::new(cards, diagonals);
+    let mut rng = Rng::new(seed);
+    let mut firsts = vec![0; cards.len()];
+    let mut order = nums.to_vec();
+    for _ in 0..trials {
+        rng.shuffle(&mut order[fixed..]);
+        let wins = bingo.play(&order);
+        if let Some(first) = wins.first() {
+            wins.iter()
+                .take_while(|w| w.draw == first.draw)
+                .for_each(|w| firsts[w.card] += 1);
+        }
+    }
+    firsts
 }
 
-fn solve2(nums: &Vec<i64>, cards: &Vec<[[i64; 5]; 5]>) -> i64 {
-    let n = cards.len();
-    let mut marks: Vec<[[bool; 5]; 5]> = Vec::with_capacity(n);
-    for _i in 0..n {
-        marks.push([[false; 5]; 5]);
+fn analyze(nums: &[i64], cards: &[Card], fixed: usize, trials: usize, seed: u64, diagonals: bool) {
+    let (drawn, pool) = nums.split_at(fixed.min(nums.len()));
+    println!("after {} draws:", drawn.len());
+    println!("card  needs  draw                 first-win");
+    let firsts = monte_carlo(nums, cards, drawn.len(), trials, seed, diagonals);
+    for (k, card) in cards.iter().enumerate() {
+        let p = firsts[k] as f64 / trials as f64;
+        match earliest_win(card, drawn, pool, diagonals) {
+            Some((m, missing)) => {
+                let missing = missing.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                println!("{:4} {:6}  {:<20} {:.4}", k, m, missing.join(","), p)
+            }
+            None => println!("{:4}  never{:22}{:.4}", k, "", p),
+        }
+    }
+    if let Some(k) = (0..cards.len()).max_by_key(|&k| (firsts[k], std::cmp::Reverse(k))) {
+        println!("favoured: card {}", k);
     }
+}
 
-    let mut mycards = cards
-        .iter()
-        .enumerate()
-        .map(|(i, c)| (i, c.clone()))
-        .collect::<Vec<(_, _)>>();
-    nums.iter()
-        .filter_map(|x| {
-            let before_count = mycards.len();
-            let losing_cards = mycards
-                .iter()
-                .map(|(i, c)| (*i, c.clone()))
-                .filter(|(i, c)| {
-                    for a in 0..5 {
-                        for b in 0..5 {
-                            if c[a][b] == *x {
-                                marks[*i][a][b] = true;
-                            }
-                        }
-                    }
+// Random puzzle input: `count` cards of size x size distinct numbers drawn
+// from 0..size*size*4, and a shuffled draw list of all of them.
+fn generate(count: usize, size: usize, seed: u64) {
+    let mut rng = Rng::new(seed);
+    let mut nums = (0..(size * size * 4) as i64).collect::<Vec<_>>();
+    rng.shuffle(&mut nums);
+    println!(
+        "{}",
+        nums.iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    for _ in 0..count {
+        rng.shuffle(&mut nums);
+        println!();
+        for row in nums[..size * size].chunks(size) {
+            let row = row.iter().map(|x| format!("{:2}", x)).collect::<Vec<_>>();
+            println!("{}", row.join(" "));
+        }
+    }
+}
 
-                    got_bingo(c, &marks[*i]).is_none()
-                })
-                .collect::<Vec<(_, _)>>();
+// Both None when no card wins before the draws run out.
+fn solve1(nums: &Vec<i64>, cards: &Vec<Card>) -> Option<i64> {
+    Bingo::new(cards, false).play(nums).first().map(|w| w.score)
+}
 
-            if losing_cards.len() == 0 {
-                if before_count > 1 {
-                    println!("multiple losing cards!");
-                }
-                let c = mycards[0].1;
-                let i = mycards[0].0;
-                got_bingo(&c, &marks[i]).map(|s| s * *x)
-            } else {
-                mycards = losing_cards;
-                None
-            }
-        })
-        .nth(0)
-        .unwrap()
+fn solve2(nums: &Vec<i64>, cards: &Vec<Card>) -> Option<i64> {
+    let wins = Bingo::new(cards, false).play(nums);
+    if wins.len() != cards.len() {
+        eprintln!("{} cards never win!", cards.len() - wins.len());
+    }
+    wins.last().map(|w| w.score)
 }
 
 fn main() -> Result<(), io::Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |f: &str, default: u64| {
+        args.iter()
+            .position(|a| a == f)
+            .map_or(default, |i| args[i + 1].parse().unwrap())
+    };
+
+    // --generate N: print a random input with N cards (--size, --seed).
+    if args.iter().any(|a| a == "--generate") {
+        generate(
+            arg("--generate", 0) as usize,
+            arg("--size", 5) as usize,
+            arg("--seed", 1),
+        );
+        return Ok(());
+    }
+
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let (nums, cards) = parse_input(&v);
-    println!("{}", solve1(&nums, &cards));
-    println!("{}", solve2(&nums, &cards));
+
+    // --ranking [--diagonals]: every winning card in order.
+    let diagonals = args.iter().any(|a| a == "--diagonals");
+    if args.iter().any(|a| a == "--ranking") {
+        println!("rank card draw number score");
+        for (i, w) in Bingo::new(&cards, diagonals).play(&nums).iter().enumerate() {
+            println!(
+                "{:4} {:4} {:4} {:6} {:5}",
+                i + 1,
+                w.card,
+                w.draw,
+                nums[w.draw],
+                w.score
+            );
+        }
+        return Ok(());
+    }
+
+    // --analyze [--after D] [--trials T] [--seed S]: per-card earliest
+    // possible win after the first D draws, and Monte Carlo first-win odds
+    // over random orders of the remaining numbers.
+    if args.iter().any(|a| a == "--analyze") {
+        analyze(
+            &nums,
+            &cards,
+            arg("--after", 0) as usize,
+            arg("--trials", 1000) as usize,
+            arg("--seed", 1),
+            diagonals,
+        );
+        return Ok(());
+    }
+
+    for r in [solve1(&nums, &cards), solve2(&nums, &cards)] {
+        match r {
+            Some(n) => println!("{}", n),
+            None => println!("no card wins"),
+        }
+    }
     Ok(())
 }
@@ -1,3 +1,7 @@
+use rng::Rng;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
 use std::io::{self, BufRead};
 
 #[derive(Clone, Debug)]
@@ -34,6 +38,33 @@ impl Line {
         }
         self.0.y > self.1.y
     }
+
+    // Primitive lattice step from self.0 to self.1 and the number of steps;
+    // the line covers self.0 + k * step for k in 0..=n.
+    fn step(&self) -> ((i64, i64), i64) {
+        let (dx, dy) = (self.1.x - self.0.x, self.1.y - self.0.y);
+        let g = gcd(dx.abs(), dy.abs());
+        if g == 0 {
+            ((0, 0), 0)
+        } else {
+            ((dx / g, dy / g), g)
+        }
+    }
+
+    // Lattice points covered, in order.
+    fn points(&self) -> impl Iterator<Item = (i64, i64)> {
+        let ((sx, sy), n) = self.step();
+        let (x, y) = (self.0.x, self.0.y);
+        (0..=n).map(move |k| (x + k * sx, y + k * sy))
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
 fn parse_input(lines: &Vec<String>) -> Vec<Line> {
@@ -133,11 +164,386 @@ fn solve2(lines: &Vec<Line>) -> i64 {
     count
 }
 
+// Identifies the infinite line through a segment: its primitive direction
+// (normalised by `Line::normalize`) and the cross product of any of its
+// points with that direction.
+type LineKey = ((i64, i64), i128);
+
+fn line_key(l: &Line) -> Option<LineKey> {
+    let ((dx, dy), n) = l.step();
+    if n == 0 {
+        return None;
+    }
+    Some((
+        (dx, dy),
+        dy as i128 * l.0.x as i128 - dx as i128 * l.0.y as i128,
+    ))
+}
+
+// Position of a point along a line with direction d. Consecutive lattice
+// points differ by |d|^2.
+fn along((dx, dy): (i64, i64), (x, y): (i64, i64)) -> i128 {
+    x as i128 * dx as i128 + y as i128 * dy as i128
+}
+
+// The single lattice point shared by two non-collinear lines, if any.
+fn crossing(a: &Line, b: &Line) -> Option<(i64, i64)> {
+    let ((ux, uy), n) = a.step();
+    let ((vx, vy), m) = b.step();
+    let (px, py, qx, qy) = (a.0.x as i128, a.0.y as i128, b.0.x as i128, b.0.y as i128);
+    let (ux, uy, vx, vy) = (ux as i128, uy as i128, vx as i128, vy as i128);
+    let (n, m) = (n as i128, m as i128);
+    if n == 0 || m == 0 {
+        // A single point: check it lies on the other line's lattice.
+        let (p, l, (sx, sy), k) = if n == 0 {
+            (a, b, (vx, vy), m)
+        } else {
+            (b, a, (ux, uy), n)
+        };
+        let (ex, ey) = ((p.0.x - l.0.x) as i128, (p.0.y - l.0.y) as i128);
+        if (sx, sy) == (0, 0) {
+            return if (ex, ey) == (0, 0) {
+                Some((p.0.x, p.0.y))
+            } else {
+                None
+            };
+        }
+        let t = if sx != 0 { ex / sx } else { ey / sy };
+        return if ex == t * sx && ey == t * sy && 0 <= t && t <= k {
+            Some((p.0.x, p.0.y))
+        } else {
+            None
+        };
+    }
+    let den = ux * vy - uy * vx;
+    if den == 0 {
+        return None;
+    }
+    let (ex, ey) = (qx - px, qy - py);
+    let tn = ex * vy - ey * vx;
+    let sn = ex * uy - ey * ux;
+    if tn % den != 0 || sn % den != 0 {
+        return None;
+    }
+    let (t, s) = (tn / den, sn / den);
+    if 0 <= t && t <= n && 0 <= s && s <= m {
+        Some(((px + t * ux) as i64, (py + t * uy) as i64))
+    } else {
+        None
+    }
+}
+
+// Counts lattice points covered by at least two lines without rasterising:
+// a sweep over x finds every pair of lines with overlapping x ranges;
+// collinear pairs contribute overlap intervals along their shared line and
+// other pairs at most one crossing point.
+fn sweep_count(lines: &[Line]) -> i64 {
+    let mut order: Vec<&Line> = lines.iter().collect();
+    order.sort_by_key(|l| l.0.x);
+    let mut active: Vec<&Line> = vec![];
+    let mut overlaps: HashMap<LineKey, Vec<(i128, i128)>> = HashMap::new();
+    // Crossing points, keyed by every line they were found on.
+    let mut crossings: HashMap<Option<LineKey>, HashSet<(i64, i64)>> = HashMap::new();
+    let mut points: HashSet<(i64, i64)> = HashSet::new();
+    for l in order {
+        // normalize() puts the smaller x first, so l.0.x is its leftmost x.
+        active.retain(|a| a.1.x >= l.0.x);
+        let key = line_key(l);
+        for a in active.iter() {
+            let akey = line_key(a);
+            if let Some(k) = key.filter(|_| key == akey) {
+                let (d, _) = k;
+                let lo = along(d, (l.0.x, l.0.y)).max(along(d, (a.0.x, a.0.y)));
+                let hi = along(d, (l.1.x, l.1.y)).min(along(d, (a.1.x, a.1.y)));
+                if lo <= hi {
+                    overlaps.entry(k).or_default().push((lo, hi));
+                }
+            } else if let Some(p) = crossing(l, a) {
+                points.insert(p);
+                crossings.entry(key).or_default().insert(p);
+                crossings.entry(akey).or_default().insert(p);
+            }
+        }
+        active.push(l);
+    }
+
+    // A point may lie in the overlaps of several lines (where they cross);
+    // it must only be counted once.
+    let mut covered: HashMap<(i64, i64), i64> = HashMap::new();
+    let mut count = 0;
+    for (key, mut iv) in overlaps {
+        let ((dx, dy), _) = key;
+        let gap = (dx * dx + dy * dy) as i128;
+        iv.sort_unstable();
+        let mut merged: Vec<(i128, i128)> = vec![];
+        for (lo, hi) in iv {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        count += merged
+            .iter()
+            .map(|(lo, hi)| (hi - lo) / gap + 1)
+            .sum::<i128>() as i64;
+        for p in crossings.get(&Some(key)).into_iter().flatten() {
+            let t = along((dx, dy), *p);
+            let i = merged.partition_point(|iv| iv.1 < t);
+            if i < merged.len() && merged[i].0 <= t {
+                *covered.entry(*p).or_default() += 1;
+            }
+        }
+    }
+    let isolated = points.iter().filter(|p| !covered.contains_key(p)).count() as i64;
+    count + isolated - covered.values().map(|n| n - 1).sum::<i64>()
+}
+
+// Reference count by walking every lattice point of every line.
+fn raster_count(lines: &[Line]) -> i64 {
+    let mut c: HashMap<(i64, i64), i64> = HashMap::new();
+    lines
+        .iter()
+        .flat_map(|l| l.points())
+        .for_each(|p| *c.entry(p).or_default() += 1);
+    c.values().filter(|n| **n > 1).count() as i64
+}
+
+// Up to 40 random lines in a 5, 20 or 100 square, any angle, normalized.
+fn random_lines(rng: &mut Rng) -> Vec<Line> {
+    let n = 1 + rng.below(40);
+    let size = [5, 20, 100][rng.below(3)];
+    (0..n)
+        .map(|_| {
+            let mut p = [0; 4];
+            p.iter_mut().for_each(|v| *v = rng.range(0, size - 1));
+            // Bias towards the puzzle's horizontal, vertical and 45 degree
+            // lines so that collinear overlaps are common.
+            match rng.below(4) {
+                0 => p[2] = p[0],
+                1 => p[3] = p[1],
+                2 => p[3] = p[1] + (p[2] - p[0]),
+                _ => {}
+            }
+            let mut l = Line(Pt { x: p[0], y: p[1] }, Pt { x: p[2], y: p[3] });
+            l.normalize();
+            l
+        })
+        .collect()
+}
+
+// Compares `sweep_count` with the raster versions on the input and on
+// random sets of lines at arbitrary angles.
+fn verify(input: &Vec<Line>, trials: usize) {
+    let hv = input
+        .iter()
+        .filter(|x| x.ish() || x.isv())
+        .cloned()
+        .collect::<Vec<_>>();
+    println!(
+        "input part 1: raster {} sweep {}",
+        solve1(input),
+        sweep_count(&hv)
+    );
+    println!(
+        "input part 2: raster {} sweep {}",
+        solve2(input),
+        sweep_count(input)
+    );
+    let mut rng = Rng::new(5);
+    let mut failures = 0;
+    for _ in 0..trials {
+        let lines = random_lines(&mut rng);
+        if sweep_count(&lines) != raster_count(&lines) {
+            failures += 1;
+            println!("mismatch: {:?}", lines);
+        }
+    }
+    println!("{} random sets, {} failures", trials, failures);
+}
+
+// Number of lines covering each cell of the bounding box, row-major.
+fn coverage(lines: &[Line]) -> (usize, usize, Vec<u32>) {
+    let w = lines.iter().map(|l| l.0.x.max(l.1.x)).max().unwrap_or(0) as usize + 1;
+    let h = lines.iter().map(|l| l.0.y.max(l.1.y)).max().unwrap_or(0) as usize + 1;
+    let mut c = vec![0; w * h];
+    lines
+        .iter()
+        .flat_map(|l| l.points())
+        .for_each(|(x, y)| c[y as usize * w + x as usize] += 1);
+    (w, h, c)
+}
+
+// Colour for n covering lines out of at most max: black for none, then
+// blue, red, yellow and white as coverage grows.
+fn ramp(n: u32, max: u32) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 255.0],
+        [255.0, 0.0, 0.0],
+        [255.0, 255.0, 0.0],
+        [255.0, 255.0, 255.0],
+    ];
+    if n == 0 {
+        return [0, 0, 0];
+    }
+    // Spread 1..=max over the stops after black.
+    let t = if max > 1 {
+        (n - 1) as f64 / (max - 1) as f64
+    } else {
+        1.0
+    } * 3.0;
+    let i = (t.floor() as usize).min(2);
+    let f = t - i as f64;
+    let (a, b) = (STOPS[i + 1], STOPS[i + 2]);
+    [0, 1, 2].map(|k| (a[k] + (b[k] - a[k]) * f).round() as u8)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// Minimal PNG encoder: 8-bit RGB, zlib stream of uncompressed blocks.
+fn png(w: usize, h: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(h * (w * 3 + 1));
+    for row in rgb.chunks(w * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut z = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, b) in blocks.iter().enumerate() {
+        z.push((i + 1 == blocks.len()) as u8);
+        z.extend((b.len() as u16).to_le_bytes());
+        z.extend((!(b.len() as u16)).to_le_bytes());
+        z.extend_from_slice(b);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in raw.iter() {
+        a = (a + x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    z.extend(((b << 16) | a).to_be_bytes());
+
+    let mut ihdr = vec![];
+    ihdr.extend((w as u32).to_be_bytes());
+    ihdr.extend((h as u32).to_be_bytes());
+    ihdr.extend([8, 2, 0, 0, 0]);
+
+    let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    for (kind, data) in [(b"IHDR", ihdr), (b"IDAT", z), (b"IEND", vec![])] {
+        out.extend((data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(&data);
+        let crc = crc32(&out[start..]);
+        out.extend(crc.to_be_bytes());
+    }
+    out
+}
+
+// Writes the coverage of `lines` as a PNG (colour ramp) or, for a .pgm
+// path, a greyscale PGM scaled to the maximum coverage.
+fn heatmap(lines: &[Line], path: &str) -> io::Result<()> {
+    let (w, h, c) = coverage(lines);
+    let max = c.iter().copied().max().unwrap_or(0).max(1);
+    let data = if path.ends_with(".pgm") {
+        let mut out = format!("P5\n{} {}\n255\n", w, h).into_bytes();
+        out.extend(c.iter().map(|n| (n * 255 / max) as u8));
+        out
+    } else {
+        let rgb: Vec<u8> = c.iter().flat_map(|n| ramp(*n, max)).collect();
+        png(w, h, &rgb)
+    };
+    fs::write(path, data)
+}
+
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // --heatmap FILE [--part 1]: coverage image; part 1 uses horizontal and
+    // vertical lines only.
+    if let Some(i) = args.iter().position(|a| a == "--heatmap") {
+        let part1 = args.windows(2).any(|w| w[0] == "--part" && w[1] == "1");
+        let selected = lines
+            .iter()
+            .filter(|x| !part1 || x.ish() || x.isv())
+            .cloned()
+            .collect::<Vec<_>>();
+        return heatmap(&selected, &args[i + 1]);
+    }
+
+    // --sweep: count without a grid (any coordinates and angles);
+    // --verify N: compare both methods.
+    if let Some(i) = args.iter().position(|a| a == "--verify") {
+        verify(&lines, args[i + 1].parse().unwrap());
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--sweep") {
+        let hv = lines
+            .iter()
+            .filter(|x| x.ish() || x.isv())
+            .cloned()
+            .collect::<Vec<_>>();
+        println!("{}", sweep_count(&hv));
+        println!("{}", sweep_count(&lines));
+        return Ok(());
+    }
+
     println!("{}", solve1(&lines));
     println!("{}", solve2(&lines));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(src: &str) -> Vec<Line> {
+        parse_input(&src.lines().map(String::from).collect())
+    }
+
+    #[test]
+    fn sweep_matches_raster_on_input() {
+        let input = lines(include_str!("../input"));
+        let hv: Vec<Line> = input
+            .iter()
+            .filter(|l| l.ish() || l.isv())
+            .cloned()
+            .collect();
+        assert_eq!(sweep_count(&hv), raster_count(&hv));
+        assert_eq!(sweep_count(&input), raster_count(&input));
+    }
+
+    #[test]
+    fn sweep_matches_raster_on_random_lines() {
+        let mut rng = Rng::new(39);
+        for _ in 0..500 {
+            let ls = random_lines(&mut rng);
+            assert_eq!(sweep_count(&ls), raster_count(&ls), "{:?}", ls);
+        }
+    }
+
+    #[test]
+    fn lattice_walks_at_any_angle() {
+        // Collinear 2:1 walks sharing (2,1), (4,2) and (6,3).
+        assert_eq!(sweep_count(&lines("0,0 -> 6,3\n2,1 -> 8,4")), 3);
+        // Crossing on the lattice point (2,1).
+        assert_eq!(sweep_count(&lines("0,0 -> 4,2\n0,2 -> 4,0")), 1);
+        // Crossing at (1.5, 0.5), which neither walk visits.
+        assert_eq!(sweep_count(&lines("0,0 -> 3,1\n0,1 -> 3,0")), 0);
+    }
+}
@@ -0,0 +1,286 @@
+//! Heightmap analysis: low points, basins, and how water flows downhill.
+//! Heights at or above a map's wall threshold (9 in the puzzle) take no
+//! part in any basin or flow.
+
+use std::collections::HashMap;
+
+pub fn parse_input(lines: &Vec<String>) -> Vec<Vec<u8>> {
+    lines
+        .iter()
+        .map(|s| s.chars().map(|x| x.to_digit(10).unwrap() as u8).collect())
+        .collect()
+}
+
+/// Cells strictly lower than all of their neighbours.
+pub fn low_points(h: &[Vec<u8>]) -> Vec<(usize, usize)> {
+    (0..h.len())
+        .into_iter()
+        .map(|i| (0..h[0].len()).into_iter().map(move |j| (i, j)))
+        .flatten()
+        .filter_map(|(i, j)| {
+            if i > 0 && h[i - 1][j] <= h[i][j] {
+                None
+            } else if i + 1 < h.len() && h[i + 1][j] <= h[i][j] {
+                None
+            } else if j > 0 && h[i][j - 1] <= h[i][j] {
+                None
+            } else if j + 1 < h[0].len() && h[i][j + 1] <= h[i][j] {
+                None
+            } else {
+                Some((i, j))
+            }
+        })
+        .collect()
+}
+
+pub type Cell = (usize, usize);
+
+/// A heightmap where heights `>= wall` are walls.
+pub struct Heightmap<'a> {
+    pub h: &'a [Vec<u8>],
+    pub wall: u8,
+}
+
+// Union-find over cell indices, with union by size and path halving.
+struct Dsu {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl Dsu {
+    fn new(n: usize) -> Self {
+        Dsu {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (a, b) = if self.size[a] < self.size[b] {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+pub struct Basin {
+    /// Usually exactly one, but a basin not split by walls can hold several.
+    pub low: Vec<Cell>,
+    pub size: usize,
+    pub top_left: Cell,
+    pub bottom_right: Cell,
+}
+
+pub struct Basins {
+    /// Basin id of every cell, None for walls.
+    pub label: Vec<Vec<Option<usize>>>,
+    pub basins: Vec<Basin>,
+    /// Cells with strictly descending routes to more than one low point.
+    pub shared: Vec<Cell>,
+}
+
+/// Where water goes from each cell. Every field is None for walls.
+pub struct Flow {
+    /// The steepest-descent neighbour, None where no neighbour is lower.
+    pub down: Vec<Vec<Option<Cell>>>,
+    /// The cell that following `down` ends at: a low point, or a flat
+    /// spot with no lower neighbour.
+    pub sink: Vec<Vec<Option<Cell>>>,
+    /// The drainage tree: the cells whose `down` is this one.
+    pub upstream: Vec<Vec<Vec<Cell>>>,
+}
+
+impl Flow {
+    /// Every cell draining through `p`, including `p` itself.
+    pub fn catchment(&self, p: Cell) -> Vec<Cell> {
+        let mut cells = vec![p];
+        let mut i = 0;
+        while i < cells.len() {
+            let (x, y) = cells[i];
+            cells.extend(&self.upstream[x][y]);
+            i += 1;
+        }
+        cells
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Drain {
+    Nowhere,
+    To(usize),
+    Many,
+}
+
+impl Heightmap<'_> {
+    pub fn size(&self) -> (usize, usize) {
+        (self.h.len(), self.h[0].len())
+    }
+
+    pub fn is_wall(&self, (i, j): Cell) -> bool {
+        self.h[i][j] >= self.wall
+    }
+
+    /// The in-bounds, non-wall neighbours of `p`.
+    pub fn neighbors(&self, (i, j): Cell) -> impl Iterator<Item = Cell> + '_ {
+        let (n, m) = self.size();
+        [
+            (i.wrapping_sub(1), j),
+            (i + 1, j),
+            (i, j.wrapping_sub(1)),
+            (i, j + 1),
+        ]
+        .into_iter()
+        .filter(move |&(x, y)| x < n && y < m && !self.is_wall((x, y)))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        let (n, m) = self.size();
+        (0..n)
+            .flat_map(move |i| (0..m).map(move |j| (i, j)))
+            .filter(|&p| !self.is_wall(p))
+    }
+
+    // Non-wall cells, lowest first.
+    fn ascending(&self) -> Vec<Cell> {
+        let mut cells: Vec<Cell> = self.cells().collect();
+        cells.sort_by_key(|&(i, j)| self.h[i][j]);
+        cells
+    }
+
+    /// Non-wall cells strictly lower than all of their neighbours.
+    pub fn low_points(&self) -> Vec<Cell> {
+        low_points(self.h)
+            .into_iter()
+            .filter(|&p| !self.is_wall(p))
+            .collect()
+    }
+
+    /// The lowest neighbour strictly below `p`; ties go to the first of
+    /// up, down, left, right.
+    pub fn steepest_descent(&self, p: Cell) -> Option<Cell> {
+        let height = |(i, j): Cell| self.h[i][j];
+        self.neighbors(p)
+            .filter(|&q| height(q) < height(p))
+            .min_by_key(|&q| height(q))
+    }
+
+    pub fn flow(&self) -> Flow {
+        let (n, m) = self.size();
+        let mut flow = Flow {
+            down: vec![vec![None; m]; n],
+            sink: vec![vec![None; m]; n],
+            upstream: vec![vec![vec![]; m]; n],
+        };
+        // Lowest first, so the sink below each cell is already known.
+        for (i, j) in self.ascending() {
+            match self.steepest_descent((i, j)) {
+                Some((x, y)) => {
+                    flow.down[i][j] = Some((x, y));
+                    flow.sink[i][j] = flow.sink[x][y];
+                    flow.upstream[x][y].push((i, j));
+                }
+                None => flow.sink[i][j] = Some((i, j)),
+            }
+        }
+        flow
+    }
+
+    /// Labels every non-wall cell with its basin: the region of cells
+    /// joined without crossing a wall. Ids follow the row-major order of
+    /// first cells.
+    pub fn basins(&self) -> Basins {
+        let (n, m) = self.size();
+        let mut dsu = Dsu::new(n * m);
+        for (i, j) in self.cells() {
+            if i + 1 < n && !self.is_wall((i + 1, j)) {
+                dsu.union(i * m + j, (i + 1) * m + j);
+            }
+            if j + 1 < m && !self.is_wall((i, j + 1)) {
+                dsu.union(i * m + j, i * m + j + 1);
+            }
+        }
+
+        let mut ids = HashMap::new();
+        let mut label = vec![vec![None; m]; n];
+        let mut basins: Vec<Basin> = vec![];
+        for (i, j) in self.cells() {
+            let id = *ids.entry(dsu.find(i * m + j)).or_insert_with(|| {
+                basins.push(Basin {
+                    low: vec![],
+                    size: 0,
+                    top_left: (i, j),
+                    bottom_right: (i, j),
+                });
+                basins.len() - 1
+            });
+            let b = &mut basins[id];
+            b.size += 1;
+            b.top_left = (b.top_left.0.min(i), b.top_left.1.min(j));
+            b.bottom_right = (b.bottom_right.0.max(i), b.bottom_right.1.max(j));
+            label[i][j] = Some(id);
+        }
+
+        let lows = self.low_points();
+        for &(i, j) in &lows {
+            if let Some(id) = label[i][j] {
+                basins[id].low.push((i, j));
+            }
+        }
+        Basins {
+            label,
+            basins,
+            shared: self.shared_cells(&lows),
+        }
+    }
+
+    // Cells from which strictly descending steps reach more than one low
+    // point. Cells are visited lowest first, so every lower neighbour is
+    // settled by the time a cell merges their destinations.
+    fn shared_cells(&self, lows: &[Cell]) -> Vec<Cell> {
+        let (n, m) = self.size();
+        let mut drain = vec![vec![Drain::Nowhere; m]; n];
+        for (k, &(i, j)) in lows.iter().enumerate() {
+            drain[i][j] = Drain::To(k);
+        }
+
+        let mut shared = vec![];
+        for (i, j) in self.ascending() {
+            if drain[i][j] != Drain::Nowhere {
+                continue;
+            }
+            let mut d = Drain::Nowhere;
+            for (x, y) in self.neighbors((i, j)) {
+                if self.h[x][y] >= self.h[i][j] {
+                    continue;
+                }
+                d = match (d, drain[x][y]) {
+                    (d, Drain::Nowhere) => d,
+                    (Drain::Nowhere, e) => e,
+                    (Drain::To(a), Drain::To(b)) if a == b => d,
+                    _ => Drain::Many,
+                };
+            }
+            if d == Drain::Many {
+                shared.push((i, j));
+            }
+            drain[i][j] = d;
+        }
+        shared.sort_unstable();
+        shared
+    }
+}
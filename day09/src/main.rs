@@ -1,34 +1,9 @@
-use std::collections::VecDeque;
+use day09::{low_points, parse_input, Basins, Heightmap};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::io::{self, BufRead};
 
-fn parse_input(lines: &Vec<String>) -> Vec<Vec<u8>> {
-    lines
-        .iter()
-        .map(|s| s.chars().map(|x| x.to_digit(10).unwrap() as u8).collect())
-        .collect()
-}
-
-fn low_points(h: &Vec<Vec<u8>>) -> Vec<(usize, usize)> {
-    (0..h.len())
-        .into_iter()
-        .map(|i| (0..h[0].len()).into_iter().map(move |j| (i, j)))
-        .flatten()
-        .filter_map(|(i, j)| {
-            if i > 0 && h[i - 1][j] <= h[i][j] {
-                None
-            } else if i + 1 < h.len() && h[i + 1][j] <= h[i][j] {
-                None
-            } else if j > 0 && h[i][j - 1] <= h[i][j] {
-                None
-            } else if j + 1 < h[0].len() && h[i][j + 1] <= h[i][j] {
-                None
-            } else {
-                Some((i, j))
-            }
-        })
-        .collect()
-}
-
 fn solve1(h: &Vec<Vec<u8>>) -> u64 {
     low_points(h)
         .iter()
@@ -37,43 +12,92 @@ fn solve1(h: &Vec<Vec<u8>>) -> u64 {
 }
 
 fn solve2(h: &Vec<Vec<u8>>) -> u64 {
-    let dxs = [-1i32, 0, 1];
-    let dys = [-1i32, 0, 1];
-    let mut basins = low_points(h)
+    let map = Heightmap { h, wall: 9 };
+    let mut sizes: Vec<u64> = map.basins().basins.iter().map(|b| b.size as u64).collect();
+    sizes.sort_unstable();
+    let n = sizes.len();
+    sizes[n - 1] * sizes[n - 2] * sizes[n - 3]
+}
+
+fn print_basins(b: &Basins) {
+    for (id, basin) in b.basins.iter().enumerate() {
+        let low: Vec<String> = basin
+            .low
+            .iter()
+            .map(|(i, j)| format!("{},{}", i, j))
+            .collect();
+        println!(
+            "basin {}: low {} size {} rows {}..={} cols {}..={}",
+            id,
+            low.join(" "),
+            basin.size,
+            basin.top_left.0,
+            basin.bottom_right.0,
+            basin.top_left.1,
+            basin.bottom_right.1
+        );
+    }
+    let shared: Vec<String> = b
+        .shared
         .iter()
-        .map(|(i, j)| {
-            let mut q = VecDeque::from([(*i as i32, *j as i32)]);
-            let mut seen = vec![vec![false; h[0].len()]; h.len()];
-            let mut basin_size = 0;
-            while q.len() > 0 {
-                let (x, y) = q.pop_front().unwrap();
-                if seen[x as usize][y as usize] {
-                    continue;
-                }
-                seen[x as usize][y as usize] = true;
-                basin_size += 1;
-                for dx in dxs {
-                    for dy in dys {
-                        if dx.abs() + dy.abs() == 1 {
-                            let px = (x + dx) as i32;
-                            let py = (y + dy) as i32;
-                            if 0 <= px && px < h.len() as i32 && 0 <= py && py < h[0].len() as i32 {
-                                if h[px as usize][py as usize] != 9
-                                    && !seen[px as usize][py as usize]
-                                {
-                                    q.push_back((px as i32, py as i32));
-                                }
-                            }
-                        }
-                    }
+        .map(|(i, j)| format!("{},{}", i, j))
+        .collect();
+    if shared.is_empty() {
+        println!("shared: none");
+    } else {
+        println!("shared: {}", shared.join(" "));
+    }
+}
+
+// Writes the labelled map as a binary PPM: a colour per basin, walls in
+// black and cells draining to several low points in white.
+fn write_ppm(b: &Basins, file: &str) -> io::Result<()> {
+    let (n, m) = (b.label.len(), b.label[0].len());
+    let shared: HashSet<_> = b.shared.iter().collect();
+    let mut out = format!("P6\n{} {}\n255\n", m, n).into_bytes();
+    for i in 0..n {
+        for j in 0..m {
+            let rgb = match b.label[i][j] {
+                _ if shared.contains(&(i, j)) => [255, 255, 255],
+                None => [0, 0, 0],
+                // Spread neighbouring ids over the hue range.
+                Some(id) => [
+                    (64 + id * 97 % 160) as u8,
+                    (64 + id * 53 % 160) as u8,
+                    (64 + id * 29 % 160) as u8,
+                ],
+            };
+            out.extend(rgb);
+        }
+    }
+    fs::write(file, out)
+}
+
+// Draws the steepest-descent direction of every cell ('*' where water
+// stops, '#' for walls), then the number of cells draining to each sink.
+fn print_flow(map: &Heightmap) {
+    let flow = map.flow();
+    let (n, m) = map.size();
+    let mut sinks = vec![];
+    for i in 0..n {
+        let row: String = (0..m)
+            .map(|j| match flow.down[i][j] {
+                _ if map.is_wall((i, j)) => '#',
+                None => {
+                    sinks.push((i, j));
+                    '*'
                 }
-            }
-            basin_size
-        })
-        .collect::<Vec<u64>>();
-    basins.sort_unstable();
-    let n = basins.len();
-    basins[n - 1] * basins[n - 2] * basins[n - 3]
+                Some((x, _)) if x < i => '^',
+                Some((x, _)) if x > i => 'v',
+                Some((_, y)) if y < j => '<',
+                Some(_) => '>',
+            })
+            .collect();
+        println!("{}", row);
+    }
+    for (i, j) in sinks {
+        println!("sink {},{}: {} cells", i, j, flow.catchment((i, j)).len());
+    }
 }
 
 fn main() -> Result<(), io::Error> {
@@ -82,5 +106,31 @@ fn main() -> Result<(), io::Error> {
     let lines = parse_input(&v);
     println!("{}", solve1(&lines));
     println!("{}", solve2(&lines));
+
+    // --basins lists every basin, --ppm FILE draws the labelled map and
+    // --flow shows where water runs. --wall H treats heights >= H as walls
+    // for these (default 9).
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args[i + 1].as_str())
+    };
+    let map = Heightmap {
+        h: &lines,
+        wall: arg("--wall").map_or(9, |w| w.parse().unwrap()),
+    };
+    if args.iter().any(|a| a == "--flow") {
+        print_flow(&map);
+    }
+    if args.iter().any(|a| a == "--basins") || arg("--ppm").is_some() {
+        let b = map.basins();
+        if args.iter().any(|a| a == "--basins") {
+            print_basins(&b);
+        }
+        if let Some(file) = arg("--ppm") {
+            write_ppm(&b, file)?;
+        }
+    }
     Ok(())
 }
@@ -1,78 +1,313 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::env;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> Vec<String> {
     lines.clone()
 }
 
-fn is_corrupt(s: &str) -> Option<i64> {
-    let score: HashMap<char, i64> = HashMap::from([(')', 3), (']', 57), ('}', 1197), ('>', 25137)]);
+// A bracket language: each opener and the closer that ends it, with the
+// syntax score of a corrupt line that stops at that closer. A closer's
+// completion score is its pair's 1-based position.
+struct Brackets {
+    pairs: Vec<(char, char)>,
+    scores: Vec<i64>,
+}
 
-    let bkts: HashMap<char, char> = HashMap::from([(')', '('), (']', '['), ('}', '{'), ('>', '<')]);
+#[derive(Debug, PartialEq)]
+enum Check {
+    Balanced,
+    // `col` is 1-based. `expected` is None when nothing was open, or when
+    // `found` is not a bracket at all.
+    Corrupt {
+        col: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    Incomplete {
+        completion: String,
+    },
+}
 
-    let mut v = vec![];
-    let mut it = s.chars();
-    while let Some(c) = it.next() {
-        if !score.contains_key(&c) {
-            v.push(c);
-        } else {
-            if v[v.len() - 1] == *bkts.get(&c).unwrap() {
-                v.pop();
+impl Brackets {
+    // Pairs written as consecutive characters, e.g. "()[]", and one syntax
+    // score per pair.
+    fn new(pairs: &str, scores: &[i64]) -> Self {
+        let cs: Vec<char> = pairs.chars().collect();
+        assert!(
+            cs.len().is_multiple_of(2),
+            "unpaired bracket in {:?}",
+            pairs
+        );
+        assert_eq!(cs.len() / 2, scores.len(), "need one score per pair");
+        Brackets {
+            pairs: cs.chunks(2).map(|p| (p[0], p[1])).collect(),
+            scores: scores.to_vec(),
+        }
+    }
+
+    fn pair_of(&self, close: char) -> Option<usize> {
+        self.pairs.iter().position(|p| p.1 == close)
+    }
+
+    fn closer(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|p| p.0 == c).map(|p| p.1)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|p| p.1 == c)
+    }
+
+    fn check(&self, s: &str) -> Check {
+        // The closers still owed, innermost last.
+        let mut owed = vec![];
+        for (i, c) in s.chars().enumerate() {
+            if let Some(close) = self.closer(c) {
+                owed.push(close);
+            } else if self.is_closer(c) && owed.last() == Some(&c) {
+                owed.pop();
             } else {
-                return Some(*score.get(&c).unwrap());
+                return Check::Corrupt {
+                    col: i + 1,
+                    expected: owed.last().copied().filter(|_| self.is_closer(c)),
+                    found: c,
+                };
+            }
+        }
+        if owed.is_empty() {
+            Check::Balanced
+        } else {
+            Check::Incomplete {
+                completion: owed.iter().rev().collect(),
             }
         }
     }
-    None
 }
 
-fn solve1(h: &Vec<String>) -> i64 {
-    h.iter().filter_map(|s| is_corrupt(s)).sum()
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Check::Balanced => write!(f, "ok"),
+            Check::Corrupt {
+                col,
+                expected: Some(e),
+                found,
+            } => write!(f, "col {}: expected {}, found {}", col, e, found),
+            Check::Corrupt { col, found, .. } => write!(f, "col {}: unexpected {}", col, found),
+            Check::Incomplete { completion } => write!(f, "incomplete, needs {}", completion),
+        }
+    }
 }
 
-fn solve2(h: &Vec<String>) -> i64 {
-    let bkts: HashMap<char, char> = HashMap::from([(')', '('), (']', '['), ('}', '{'), ('>', '<')]);
-    let bkts2: HashMap<char, char> =
-        HashMap::from([('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')]);
-    let score: HashMap<char, i64> = HashMap::from([(')', 1), (']', 2), ('}', 3), ('>', 4)]);
-    let mut scores = h
-        .iter()
-        .filter_map(|s| {
-            let mut v = vec![];
-            let mut it = s.chars();
-            while let Some(c) = it.next() {
-                if !score.contains_key(&c) {
-                    v.push(c);
-                } else {
-                    if v[v.len() - 1] == *bkts.get(&c).unwrap() {
-                        v.pop();
-                    } else {
-                        return None;
+#[derive(Clone, Copy, PartialEq)]
+enum Edit {
+    Keep(char),
+    Insert(char),
+    Delete(char),
+    Replace(char, char),
+}
+
+impl Brackets {
+    // Edits to make the two ends of a pair: the cheapest pair to turn
+    // `a` and `b` into, counting a replacement for each end that differs.
+    fn pair_cost(&self, a: char, b: char) -> (usize, (char, char)) {
+        self.pairs
+            .iter()
+            .map(|&(o, c)| ((o != a) as usize + (c != b) as usize, (o, c)))
+            .min()
+            .unwrap()
+    }
+
+    // The fewest inserts, deletes and replacements that balance `s`, by
+    // interval DP: cost[i][j] balances s[i..j], either leaving s[i] to be
+    // fixed on its own (one edit) or pairing it with some s[k].
+    fn repair(&self, s: &str) -> Vec<Edit> {
+        let cs: Vec<char> = s.chars().collect();
+        let n = cs.len();
+        let mut cost = vec![vec![0; n + 1]; n + 1];
+        let mut partner = vec![vec![None; n + 1]; n + 1];
+        for len in 1..=n {
+            for i in 0..=n - len {
+                let j = i + len;
+                cost[i][j] = 1 + cost[i + 1][j];
+                for k in i + 1..j {
+                    let c = self.pair_cost(cs[i], cs[k]).0 + cost[i + 1][k] + cost[k + 1][j];
+                    if c < cost[i][j] || (c == cost[i][j] && partner[i][j].is_none()) {
+                        cost[i][j] = c;
+                        partner[i][j] = Some(k);
                     }
                 }
             }
-            if v.len() == 0 {
-                None
+        }
+        let mut edits = vec![];
+        self.rebuild(&cs, &partner, 0, n, &mut edits);
+        edits
+    }
+
+    fn rebuild(
+        &self,
+        cs: &[char],
+        partner: &[Vec<Option<usize>>],
+        i: usize,
+        j: usize,
+        edits: &mut Vec<Edit>,
+    ) {
+        if i == j {
+            return;
+        }
+        let c = cs[i];
+        let keep = |x: char, want: char| {
+            if x == want {
+                Edit::Keep(x)
             } else {
-                Some(
-                    v.iter()
-                        .rev()
-                        .map(|c| bkts2.get(&c).unwrap())
-                        .fold(0, |acc, c| acc * 5 + score.get(c).unwrap()),
-                )
+                Edit::Replace(x, want)
+            }
+        };
+        match partner[i][j] {
+            Some(k) => {
+                let (o, cl) = self.pair_cost(c, cs[k]).1;
+                edits.push(keep(c, o));
+                self.rebuild(cs, partner, i + 1, k, edits);
+                edits.push(keep(cs[k], cl));
+                self.rebuild(cs, partner, k + 1, j, edits);
+            }
+            // An opener gets its closer at the end of the span, a closer
+            // an opener just before it; anything else goes.
+            None => {
+                if let Some(cl) = self.closer(c) {
+                    edits.push(Edit::Keep(c));
+                    self.rebuild(cs, partner, i + 1, j, edits);
+                    edits.push(Edit::Insert(cl));
+                    return;
+                }
+                match self.pairs.iter().find(|p| p.1 == c) {
+                    Some(&(o, _)) => edits.extend([Edit::Insert(o), Edit::Keep(c)]),
+                    None => edits.push(Edit::Delete(c)),
+                }
+                self.rebuild(cs, partner, i + 1, j, edits);
             }
+        }
+    }
+}
+
+// The repaired line with inserts in green, replacements in yellow and
+// deleted characters struck through in red.
+fn marked(edits: &[Edit]) -> String {
+    edits
+        .iter()
+        .map(|e| match e {
+            Edit::Keep(c) => c.to_string(),
+            Edit::Insert(c) => format!("\x1b[32m{}\x1b[0m", c),
+            Edit::Delete(c) => format!("\x1b[9;31m{}\x1b[0m", c),
+            Edit::Replace(_, c) => format!("\x1b[33m{}\x1b[0m", c),
         })
+        .collect()
+}
+
+// Repairs every unbalanced line, then totals the edits by kind and counts
+// lines by how many edits they needed.
+fn print_repairs(h: &[String], b: &Brackets) {
+    let mut totals = [0; 3];
+    let mut by_count: BTreeMap<usize, usize> = BTreeMap::new();
+    for (i, l) in h.iter().enumerate() {
+        let edits = b.repair(l);
+        let counts = [
+            edits
+                .iter()
+                .filter(|e| matches!(e, Edit::Insert(_)))
+                .count(),
+            edits
+                .iter()
+                .filter(|e| matches!(e, Edit::Delete(_)))
+                .count(),
+            edits
+                .iter()
+                .filter(|e| matches!(e, Edit::Replace(..)))
+                .count(),
+        ];
+        let n: usize = counts.iter().sum();
+        *by_count.entry(n).or_default() += 1;
+        if n == 0 {
+            continue;
+        }
+        for k in 0..3 {
+            totals[k] += counts[k];
+        }
+        println!("{}: {} edits: {}", i + 1, n, marked(&edits));
+    }
+    println!(
+        "{} inserts, {} deletes, {} replacements",
+        totals[0], totals[1], totals[2]
+    );
+    for (n, lines) in by_count {
+        println!("{} lines need {} edits", lines, n);
+    }
+}
+
+// Characters that are not brackets at all score 0.
+fn syntax_score(b: &Brackets, c: &Check) -> Option<i64> {
+    match c {
+        Check::Corrupt { found, .. } => Some(b.pair_of(*found).map_or(0, |k| b.scores[k])),
+        _ => None,
+    }
+}
+
+// The completion read as digits 1..=n in base n + 1, for n pairs.
+fn completion_score(b: &Brackets, c: &Check) -> Option<i64> {
+    let base = b.pairs.len() as i64 + 1;
+    match c {
+        Check::Incomplete { completion } => Some(
+            completion
+                .chars()
+                .fold(0, |acc, c| acc * base + b.pair_of(c).unwrap() as i64 + 1),
+        ),
+        _ => None,
+    }
+}
+
+fn solve1(h: &Vec<String>, b: &Brackets) -> i64 {
+    h.iter().filter_map(|s| syntax_score(b, &b.check(s))).sum()
+}
+
+fn solve2(h: &Vec<String>, b: &Brackets) -> i64 {
+    let mut scores = h
+        .iter()
+        .filter_map(|s| completion_score(b, &b.check(s)))
         .collect::<Vec<i64>>();
     scores.sort_unstable();
-    let n = scores.len();
-    scores[n / 2]
+    // No incomplete lines (possible with custom pairs) scores nothing.
+    scores.get(scores.len() / 2).copied().unwrap_or(0)
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // --pairs sets the bracket pairs (default "()[]{}<>") and --scores their
+    // syntax scores (default "3,57,1197,25137"). --check prints the verdict
+    // for every line and --repair the fewest edits to fix it.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args[i + 1].as_str())
+    };
+    let scores: Vec<i64> = arg("--scores")
+        .unwrap_or("3,57,1197,25137")
+        .split(',')
+        .map(|n| n.parse().unwrap())
+        .collect();
+    let brackets = Brackets::new(arg("--pairs").unwrap_or("()[]{}<>"), &scores);
+    println!("{}", solve1(&lines, &brackets));
+    println!("{}", solve2(&lines, &brackets));
+    if args.iter().any(|a| a == "--check") {
+        for (i, l) in lines.iter().enumerate() {
+            println!("{}: {}", i + 1, brackets.check(l));
+        }
+    }
+    if args.iter().any(|a| a == "--repair") {
+        print_repairs(&lines, &brackets);
+    }
     Ok(())
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::env;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> HashMap<String, Vec<String>> {
@@ -23,103 +24,432 @@ fn is_big(s: &str) -> bool {
     s.chars().nth(0).unwrap().is_uppercase()
 }
 
-fn visited(node: &str, path: &Vec<&str>) -> bool {
-    path.iter().filter(|x| **x == node).nth(0).is_some()
+// The cave graph with names interned to ids.
+struct Caves {
+    names: Vec<String>,
+    adj: Vec<Vec<usize>>,
+    big: Vec<bool>,
+    start: usize,
+    end: usize,
 }
 
-fn dfs<'a>(
-    node: &'a str,
-    h: &'a HashMap<String, Vec<String>>,
-    path: &mut Vec<&'a str>,
-    path_count: &mut i64,
-) {
-    path.push(node);
-    if node == "end" {
-        *path_count += 1;
-        path.pop();
-        return;
+impl Caves {
+    fn new(h: &HashMap<String, Vec<String>>) -> Result<Caves, String> {
+        let mut names: Vec<String> = h.keys().cloned().collect();
+        names.sort();
+        let find = |s: &str| {
+            names
+                .iter()
+                .position(|n| n == s)
+                .ok_or(format!("no {} cave", s))
+        };
+        let adj = names
+            .iter()
+            .map(|n| h[n].iter().map(|m| find(m).unwrap()).collect())
+            .collect();
+        let big = names.iter().map(|n| is_big(n)).collect();
+        let (start, end) = (find("start")?, find("end")?);
+        Ok(Caves {
+            names,
+            adj,
+            big,
+            start,
+            end,
+        })
     }
-    let nbrs = h.get(node).unwrap();
-    for nbr in nbrs.iter() {
-        let can_visit = is_big(nbr) || !visited(nbr, path);
-        if can_visit {
-            dfs(nbr, h, path, path_count);
+
+    fn path_string(&self, path: &[usize]) -> String {
+        let names = path.iter().map(|&n| self.names[n].as_str());
+        names.collect::<Vec<_>>().join(",")
+    }
+}
+
+// How often caves may be entered on one path.
+#[derive(Debug, Clone)]
+struct Policy {
+    // Visits allowed per small cave.
+    small: u32,
+    // Visits allowed per big cave; None for unlimited.
+    big: Option<u32>,
+    // Visits beyond those limits allowed over the whole path.
+    repeats: u32,
+    // Caves that may never be entered (the path still begins at start).
+    forbidden: Vec<String>,
+}
+
+impl Policy {
+    // Small caves at most once.
+    fn part1() -> Policy {
+        Policy {
+            small: 1,
+            big: None,
+            repeats: 0,
+            forbidden: vec!["start".to_string()],
+        }
+    }
+
+    // One small cave, other than start, may be visited twice.
+    fn part2() -> Policy {
+        Policy {
+            repeats: 1,
+            ..Policy::part1()
         }
     }
-    path.pop();
 }
 
-fn solve1(h: &HashMap<String, Vec<String>>) -> i64 {
-    let mut count = 0;
-    dfs("start", h, &mut vec![], &mut count);
-    count
+// A policy resolved against a cave graph.
+struct Rules<'a> {
+    caves: &'a Caves,
+    limit: Vec<Option<u32>>,
+    forbidden: Vec<bool>,
+    repeats: u32,
 }
 
-fn visited2(node: &str, path: &Vec<&str>, twice_visited: &Option<&str>) -> bool {
-    // node is a small cave.
-    let exists = path.iter().filter(|x| **x == node).nth(0).is_some();
-    // If node is not in path, we have not visited it.
-    if !exists {
-        return false;
+impl<'a> Rules<'a> {
+    fn new(caves: &'a Caves, policy: &Policy) -> Result<Rules<'a>, String> {
+        let limit: Vec<Option<u32>> = caves
+            .big
+            .iter()
+            .map(|&b| if b { policy.big } else { Some(policy.small) })
+            .collect();
+        let mut forbidden = vec![false; caves.names.len()];
+        for f in policy.forbidden.iter() {
+            if let Some(n) = caves.names.iter().position(|n| n == f) {
+                forbidden[n] = true;
+            }
+        }
+        // Two connected caves without a visit limit can be walked back and
+        // forth forever.
+        for (a, nbrs) in caves.adj.iter().enumerate() {
+            for &b in nbrs {
+                if limit[a].is_none() && limit[b].is_none() && !forbidden[a] && !forbidden[b] {
+                    return Err(format!(
+                        "caves {} and {} are connected and unlimited: infinitely many paths",
+                        caves.names[a], caves.names[b]
+                    ));
+                }
+            }
+        }
+        Ok(Rules {
+            caves,
+            limit,
+            forbidden,
+            repeats: policy.repeats,
+        })
+    }
+
+    // Whether `n` can be entered after `count` earlier visits with `budget`
+    // repeats left, and if so whether doing so uses a repeat.
+    fn enter(&self, n: usize, count: u32, budget: u32) -> Option<bool> {
+        if self.forbidden[n] {
+            return None;
+        }
+        match self.limit[n] {
+            None => Some(false),
+            Some(l) if count < l => Some(false),
+            Some(_) if budget > 0 => Some(true),
+            Some(_) => None,
+        }
+    }
+
+    // Number of paths from start to end, memoised on (cave, visit counts of
+    // limited caves packed into a u128, repeats left).
+    fn count_paths(&self) -> Result<u128, String> {
+        let max = self.limit.iter().flatten().max().copied().unwrap_or(0) + self.repeats;
+        let width = 32 - max.leading_zeros();
+        let mut offs = vec![None; self.limit.len()];
+        let mut bits = 0;
+        for (n, l) in self.limit.iter().enumerate() {
+            if l.is_some() {
+                offs[n] = Some(bits);
+                bits += width;
+            }
+        }
+        if bits > 128 {
+            return Err(format!(
+                "{} bits of visit state; at most 128 are supported",
+                bits
+            ));
+        }
+        let start = self.caves.start;
+        let state = offs[start].map_or(0, |o| 1u128 << o);
+        let mut memo = HashMap::new();
+        Ok(self.count(start, state, self.repeats, &offs, width, &mut memo))
+    }
+
+    fn count(
+        &self,
+        node: usize,
+        state: u128,
+        budget: u32,
+        offs: &[Option<u32>],
+        width: u32,
+        memo: &mut HashMap<(usize, u128, u32), u128>,
+    ) -> u128 {
+        if node == self.caves.end {
+            return 1;
+        }
+        if let Some(&n) = memo.get(&(node, state, budget)) {
+            return n;
+        }
+        let mut n = 0;
+        for &nbr in self.caves.adj[node].iter() {
+            let count = offs[nbr].map_or(0, |o| (state >> o) as u32 & ((1 << width) - 1));
+            if let Some(repeat) = self.enter(nbr, count, budget) {
+                let state = offs[nbr].map_or(state, |o| state + (1 << o));
+                n += self.count(nbr, state, budget - repeat as u32, offs, width, memo);
+            }
+        }
+        memo.insert((node, state, budget), n);
+        n
+    }
+
+    fn paths(&'a self) -> Paths<'a> {
+        let mut counts = vec![0; self.limit.len()];
+        counts[self.caves.start] = 1;
+        Paths {
+            rules: self,
+            path: vec![self.caves.start],
+            next: vec![0],
+            repeat: vec![false],
+            counts,
+            budget: self.repeats,
+        }
+    }
+}
+
+// Lazily enumerates every path from start to end, depth first, following
+// each cave's neighbours in input order.
+struct Paths<'a> {
+    rules: &'a Rules<'a>,
+    path: Vec<usize>,
+    // Index of the next neighbour to try at each depth.
+    next: Vec<usize>,
+    // Whether entering the cave at each depth used a repeat.
+    repeat: Vec<bool>,
+    counts: Vec<u32>,
+    budget: u32,
+}
+
+impl<'a> Paths<'a> {
+    fn pop(&mut self) {
+        let n = self.path.pop().unwrap();
+        self.next.pop();
+        self.counts[n] -= 1;
+        if self.repeat.pop().unwrap() {
+            self.budget += 1;
+        }
     }
-    // If node is in path, check if some (possibly other node) has already been
-    // visited twice.
-    if let Some(_) = twice_visited {
-        return true;
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        let caves = self.rules.caves;
+        loop {
+            let node = *self.path.last()?;
+            let d = self.path.len() - 1;
+            if node == caves.end || self.next[d] == caves.adj[node].len() {
+                self.pop();
+                continue;
+            }
+            let nbr = caves.adj[node][self.next[d]];
+            self.next[d] += 1;
+            if let Some(repeat) = self.rules.enter(nbr, self.counts[nbr], self.budget) {
+                self.path.push(nbr);
+                self.next.push(0);
+                self.repeat.push(repeat);
+                self.counts[nbr] += 1;
+                self.budget -= repeat as u32;
+                if nbr == caves.end {
+                    return Some(self.path.clone());
+                }
+            }
+        }
     }
-    // If not, node can be visited for a second time.
-    return false;
 }
 
-fn dfs2<'a>(
-    node: &'a str,
-    h: &'a HashMap<String, Vec<String>>,
-    path: &mut Vec<&'a str>,
-    twice_visited: &mut Option<&'a str>,
-    path_count: &mut i64,
-) {
-    // Since we got here, it is valid to visit node.
+fn count_paths(h: &HashMap<String, Vec<String>>, policy: &Policy) -> Result<u128, String> {
+    let caves = Caves::new(h)?;
+    let rules = Rules::new(&caves, policy)?;
+    rules.count_paths()
+}
 
-    // If node already is in path, it is being visited twice.
-    if !is_big(node) && visited(node, path) {
-        *twice_visited = Some(node);
+fn solve1(h: &HashMap<String, Vec<String>>) -> Result<u128, String> {
+    count_paths(h, &Policy::part1())
+}
+
+fn solve2(h: &HashMap<String, Vec<String>>) -> Result<u128, String> {
+    count_paths(h, &Policy::part2())
+}
+
+// Problems that make the cave system suspicious: caves that cannot be
+// reached from start and connected big caves (infinitely many paths).
+fn lint(caves: &Caves) -> Vec<String> {
+    let mut warnings = vec![];
+    let mut seen = vec![false; caves.names.len()];
+    let mut todo = vec![caves.start];
+    seen[caves.start] = true;
+    while let Some(n) = todo.pop() {
+        for &m in caves.adj[n].iter() {
+            if !seen[m] {
+                seen[m] = true;
+                todo.push(m);
+            }
+        }
     }
-    path.push(node);
-    if node == "end" {
-        // println!("{:?}", path);
-        *path_count += 1;
-        path.pop();
-        return;
+    for (n, name) in caves.names.iter().enumerate() {
+        if !seen[n] {
+            warnings.push(format!("cave {} is unreachable from start", name));
+        }
+        for &m in caves.adj[n].iter() {
+            if n < m && caves.big[n] && caves.big[m] {
+                warnings.push(format!(
+                    "big caves {} and {} form a cycle",
+                    name, caves.names[m]
+                ));
+            }
+        }
     }
-    let nbrs = h.get(node).unwrap();
-    // println!("{} -> {:?}", node, nbrs);
-    for nbr in nbrs.iter() {
-        // println!(
-        //     "nbr={} (node={}) -> path={:?} twice_visited={:?}",
-        //     nbr, node, path, twice_visited
-        // );
-        let can_visit = nbr != "start" && (is_big(nbr) || !visited2(nbr, path, twice_visited));
-        if can_visit {
-            dfs2(nbr, h, path, twice_visited, path_count);
+    warnings
+}
+
+// Graphviz source for the cave system: big caves are boxes, small caves
+// ellipses, start and end are filled double circles. Edges on the given
+// paths are drawn in one colour per path.
+fn dot(caves: &Caves, paths: &[Vec<usize>]) -> String {
+    const COLOURS: [&str; 6] = ["red", "blue", "darkgreen", "orange", "purple", "brown"];
+    let mut used: HashMap<(usize, usize), Vec<&str>> = HashMap::new();
+    for (i, p) in paths.iter().enumerate() {
+        for w in p.windows(2) {
+            let e = (w[0].min(w[1]), w[0].max(w[1]));
+            let c = COLOURS[i % COLOURS.len()];
+            let cs = used.entry(e).or_default();
+            if !cs.contains(&c) {
+                cs.push(c);
+            }
         }
     }
-    path.pop();
-    if *twice_visited == Some(node) {
-        *twice_visited = None;
+    let mut out = vec!["graph caves {".to_string()];
+    for (n, name) in caves.names.iter().enumerate() {
+        let style = if n == caves.start {
+            "shape=doublecircle, style=filled, fillcolor=palegreen"
+        } else if n == caves.end {
+            "shape=doublecircle, style=filled, fillcolor=lightcoral"
+        } else if caves.big[n] {
+            "shape=box, style=bold"
+        } else {
+            "shape=ellipse"
+        };
+        out.push(format!("    \"{}\" [{}];", name, style));
     }
+    for (a, nbrs) in caves.adj.iter().enumerate() {
+        for &b in nbrs.iter().filter(|&&b| a < b) {
+            let attrs = match used.get(&(a, b)) {
+                Some(cs) => format!(" [color=\"{}\", penwidth=2]", cs.join(":")),
+                None => " [color=gray]".to_string(),
+            };
+            out.push(format!(
+                "    \"{}\" -- \"{}\"{};",
+                caves.names[a], caves.names[b], attrs
+            ));
+        }
+    }
+    out.push("}".to_string());
+    out.join("\n")
 }
 
-fn solve2(h: &HashMap<String, Vec<String>>) -> i64 {
-    let mut count = 0;
-    dfs2("start", h, &mut vec![], &mut None, &mut count);
-    count
+// Prints the DOT graph with paths `offset..offset + highlight` marked;
+// warnings go to stderr.
+fn print_dot(
+    h: &HashMap<String, Vec<String>>,
+    policy: &Policy,
+    offset: usize,
+    highlight: usize,
+) -> Result<(), String> {
+    let caves = Caves::new(h)?;
+    lint(&caves)
+        .iter()
+        .for_each(|w| eprintln!("warning: {}", w));
+    let paths = match Rules::new(&caves, policy) {
+        _ if highlight == 0 => vec![],
+        Ok(rules) => rules.paths().skip(offset).take(highlight).collect(),
+        Err(e) => {
+            eprintln!("warning: not highlighting paths: {}", e);
+            vec![]
+        }
+    };
+    println!("{}", dot(&caves, &paths));
+    Ok(())
+}
+
+// Prints paths `offset..offset + limit` in enumeration order.
+fn print_paths(
+    h: &HashMap<String, Vec<String>>,
+    policy: &Policy,
+    offset: usize,
+    limit: usize,
+) -> Result<(), String> {
+    let caves = Caves::new(h)?;
+    let rules = Rules::new(&caves, policy)?;
+    for p in rules.paths().skip(offset).take(limit) {
+        println!("{}", caves.path_string(&p));
+    }
+    Ok(())
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // Policy flags: --small N, --big N (default unlimited), --repeats N,
+    // --forbid a,b (default start). --count counts paths under the policy;
+    // --paths [--offset N] [--limit N] lists them.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |f: &str| {
+        args.iter()
+            .position(|a| a == f)
+            .map(|i| args[i + 1].as_str())
+    };
+    let num = |f: &str, default: usize| arg(f).map_or(default, |n| n.parse().unwrap());
+    let policy = Policy {
+        small: num("--small", 1) as u32,
+        big: arg("--big").map(|n| n.parse().unwrap()),
+        repeats: num("--repeats", 0) as u32,
+        forbidden: arg("--forbid").map_or(vec!["start".to_string()], |f| {
+            f.split(',').map(|x| x.to_string()).collect()
+        }),
+    };
+    if args.iter().any(|a| a == "--count") {
+        match count_paths(&lines, &policy) {
+            Ok(n) => println!("{}", n),
+            Err(e) => println!("error: {}", e),
+        }
+        return Ok(());
+    }
+    // --dot [--highlight N] [--offset N]: Graphviz output.
+    if args.iter().any(|a| a == "--dot") {
+        let highlight = num("--highlight", 0);
+        if let Err(e) = print_dot(&lines, &policy, num("--offset", 0), highlight) {
+            println!("error: {}", e);
+        }
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--paths") {
+        let limit = num("--limit", usize::MAX);
+        if let Err(e) = print_paths(&lines, &policy, num("--offset", 0), limit) {
+            println!("error: {}", e);
+        }
+        return Ok(());
+    }
+
+    for r in [solve1(&lines), solve2(&lines)] {
+        match r {
+            Ok(n) => println!("{}", n),
+            Err(e) => println!("error: {}", e),
+        }
+    }
     Ok(())
 }
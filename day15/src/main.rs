@@ -1,110 +1,196 @@
-use std::cmp::Ordering;
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use search::{Dense, Graph, Search};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> Vec<Vec<char>> {
     lines.iter().map(|x| x.chars().collect()).collect()
 }
 
-#[derive(PartialEq, Eq, Debug)]
-struct Node((i64, i64), i64);
+// The risk map tiled `tiles` times in each direction: every tile right or
+// down adds 1 to the risk, wrapping 9 back to 1. Cells are computed on
+// demand, so large factors never build the expanded map. As a graph, cells
+// are numbered i * cols + j and moving onto a cell costs its risk level.
+struct Grid<'a> {
+    base: &'a [Vec<char>],
+    tiles: usize,
+}
+
+impl Grid<'_> {
+    fn size(&self) -> (usize, usize) {
+        (
+            self.base.len() * self.tiles,
+            self.base[0].len() * self.tiles,
+        )
+    }
+
+    fn risk(&self, (i, j): (usize, usize)) -> Option<i64> {
+        let (a, b) = (self.base.len(), self.base[0].len());
+        if i >= a * self.tiles || j >= b * self.tiles {
+            return None;
+        }
+        let r = self.base[i % a][j % b].to_digit(10).unwrap() as usize + i / a + j / b;
+        Some(((r - 1) % 9 + 1) as i64)
+    }
 
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.1.cmp(&other.1)
+    fn cell(&self, s: usize) -> (usize, usize) {
+        let m = self.size().1;
+        (s / m, s % m)
     }
 }
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Graph for Grid<'_> {
+    type State = usize;
+
+    fn neighbors(&self, &s: &usize) -> impl Iterator<Item = (usize, i64)> {
+        let (i, j) = self.cell(s);
+        let m = self.size().1;
+        let dx = [-1, 0, 1, 0];
+        let dy = [0, -1, 0, 1];
+        (0..4).filter_map(move |k| {
+            let p = (i.checked_add_signed(dx[k])?, j.checked_add_signed(dy[k])?);
+            Some((p.0 * m + p.1, self.risk(p)?))
+        })
     }
 }
 
-fn solve1(h: &Vec<Vec<char>>) -> i64 {
-    // Dijkstra's single source shortest paths
-    let mut to_visit = BinaryHeap::new();
-    to_visit.push(Reverse(Node((0, 0), 0)));
+#[derive(Clone, Copy)]
+enum Mode {
+    Dijkstra,
+    AStar,
+    Buckets,
+}
 
-    let dx = &[-1, 0, 1, 0];
-    let dy = &[0, -1, 0, 1];
+// Searches from the top left to `goal` (None: every cell), keeping
+// distances in flat arrays rather than one map entry per cell.
+fn lowest_risk(g: &Grid, mode: Mode, goal: Option<usize>) -> Search<usize, Dense> {
+    let (n, m) = g.size();
+    let table = Dense::new(n * m);
+    let is_goal = |s: &usize| Some(*s) == goal;
+    match mode {
+        Mode::Dijkstra => search::dijkstra_in(table, g, 0, is_goal),
+        // Every step costs at least 1, so the Manhattan distance is admissible.
+        Mode::AStar => search::astar_in(table, g, 0, is_goal, |&s| {
+            let (i, j) = g.cell(s);
+            ((n - 1 - i) + (m - 1 - j)) as i64
+        }),
+        Mode::Buckets => search::buckets_in(table, g, 0, is_goal, 9),
+    }
+}
 
-    let mut visited = HashSet::new();
-    let mut distances = HashMap::new();
-    distances.insert((0, 0), 0);
+fn lowest_total(g: &Grid, mode: Mode) -> i64 {
+    let (n, m) = g.size();
+    lowest_risk(g, mode, Some(n * m - 1)).cost().unwrap()
+}
 
-    while let Some(Reverse(Node((i, j), curr_dist))) = to_visit.pop() {
-        if !visited.insert((i, j)) {
-            // Node is already visited. Skip it.
-            continue;
-        }
+fn solve1(h: &[Vec<char>], mode: Mode) -> i64 {
+    lowest_total(&Grid { base: h, tiles: 1 }, mode)
+}
+
+// The tiled map's best route, kept whole so its path can be drawn.
+fn solve2(g: &Grid, mode: Mode) -> Search<usize, Dense> {
+    let (n, m) = g.size();
+    lowest_risk(g, mode, Some(n * m - 1))
+}
 
-        // println!(
-        //     "C: {:?}\nvisited: {:?}\nto_visit: {:?}\ndistances: {:?}\n",
-        //     Node((i, j), curr_dist),
-        //     visited,
-        //     to_visit,
-        //     distances
-        // );
-        for k in 0..4 {
-            if 0 <= i + dx[k]
-                && i + dx[k] < h.len() as i64
-                && 0 <= j + dy[k]
-                && j + dy[k] < h[0].len() as i64
-            {
-                let x = (i + dx[k]) as usize;
-                let y = (j + dy[k]) as usize;
-                let new_distance = curr_dist + h[x][y].to_digit(10).unwrap() as i64;
-                if let Some(v) = distances.get_mut(&(x, y)) {
-                    if new_distance < *v {
-                        *v = new_distance;
-                    }
-                } else {
-                    distances.insert((x, y), new_distance);
-                    to_visit.push(Reverse(Node((x as i64, y as i64), new_distance)));
-                }
+// Prints the map with the cells on `path` in bold red.
+fn show(g: &Grid, path: &HashSet<(usize, usize)>) {
+    let (n, m) = g.size();
+    for i in 0..n {
+        let mut line = String::new();
+        for j in 0..m {
+            let r = g.risk((i, j)).unwrap();
+            if path.contains(&(i, j)) {
+                line += &format!("\x1b[1;31m{}\x1b[0m", r);
+            } else {
+                line += &r.to_string();
             }
         }
+        println!("{}", line);
     }
-    *distances.get(&(h.len() - 1, h[0].len() - 1)).unwrap()
 }
 
-fn solve2(h: &Vec<Vec<char>>) -> i64 {
-    let next_char = |c: char| -> char {
-        let mut d = c.to_digit(10).unwrap();
-        d += 1;
-        if d == 10 {
-            d = 1;
-        }
-        char::from_digit(d, 10).unwrap()
-    };
-    let mut h2 = vec![vec!['0'; h[0].len() * 5]; h.len() * 5];
-    let a = h.len();
-    let b = h[0].len();
-    for i in 0..h.len() {
-        for j in 0..h[0].len() {
-            let mut c = h[i][j];
-            for k in 0..5 {
-                if k > 0 {
-                    c = next_char(h2[i + (k - 1) * a][j])
-                }
-                for l in 0..5 {
-                    h2[i + k * a][j + l * b] = c;
-                    c = next_char(c);
-                }
+// Writes the map as a binary PPM: risk as shades of grey (darker is
+// riskier), the path in red.
+fn write_ppm(g: &Grid, path: &HashSet<(usize, usize)>, file: &str) -> io::Result<()> {
+    let (n, m) = g.size();
+    let mut out = format!("P6\n{} {}\n255\n", m, n).into_bytes();
+    for i in 0..n {
+        for j in 0..m {
+            if path.contains(&(i, j)) {
+                out.extend([255, 0, 0]);
+            } else {
+                let v = 255 - 25 * g.risk((i, j)).unwrap() as u8;
+                out.extend([v, v, v]);
             }
         }
     }
+    fs::write(file, out)
+}
 
-    solve1(&h2)
+// Prints the lowest total risk of reaching every cell from the top left.
+fn print_cumulative(g: &Grid, mode: Mode) {
+    let r = lowest_risk(g, mode, None);
+    let (n, m) = g.size();
+    let dist = |i, j| r.dist(&(i * m + j)).unwrap();
+    let width = (0..n * m).map(|s| r.dist(&s).unwrap()).max().unwrap();
+    let width = width.to_string().len();
+    for i in 0..n {
+        let row: Vec<String> = (0..m)
+            .map(|j| format!("{:>w$}", dist(i, j), w = width))
+            .collect();
+        println!("{}", row.join(" "));
+    }
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // --mode dijkstra|astar|buckets picks the search (default buckets).
+    // --tiles N sets the part 2 tiling factor (default 5). --show prints
+    // that map with the best path highlighted, --ppm FILE draws it as an
+    // image and --cumulative prints the lowest total risk to every cell.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args[i + 1].as_str())
+    };
+    let has = |name: &str| args.iter().any(|a| a == name);
+    let mode = match arg("--mode") {
+        Some("dijkstra") => Mode::Dijkstra,
+        Some("astar") => Mode::AStar,
+        _ => Mode::Buckets,
+    };
+    let tiles = arg("--tiles").map_or(5, |t| t.parse().unwrap());
+    if tiles < 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--tiles must be at least 1",
+        ));
+    }
+    let g = Grid {
+        base: &lines,
+        tiles,
+    };
+    let r = solve2(&g, mode);
+    println!("{}", solve1(&lines, mode));
+    println!("{}", r.cost().unwrap());
+
+    if has("--show") || arg("--ppm").is_some() {
+        let path: HashSet<_> = r.path().unwrap().into_iter().map(|s| g.cell(s)).collect();
+        if has("--show") {
+            show(&g, &path);
+        }
+        if let Some(file) = arg("--ppm") {
+            write_ppm(&g, &path, file)?;
+        }
+    }
+    if has("--cumulative") {
+        print_cumulative(&g, mode);
+    }
     Ok(())
 }
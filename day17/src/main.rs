@@ -1,3 +1,5 @@
+use rng::Rng;
+use std::env;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> ((i64, i64), (i64, i64)) {
@@ -23,6 +25,7 @@ fn is_in_target_area(
 }
 
 fn is_good((mut vx, mut vy): &(i64, i64), target_area: &((i64, i64), (i64, i64))) -> Option<i64> {
+    let ((min_x, max_x), (min_y, _)) = *target_area;
     let mut x = 0;
     let mut y = 0;
     let mut max_y = 0;
@@ -35,11 +38,13 @@ fn is_good((mut vx, mut vy): &(i64, i64), target_area: &((i64, i64), (i64, i64))
             break Some(max_y);
         }
 
-        if y < target_area.1 .0 {
+        // Below the target and no longer rising.
+        if y < min_y && vy <= 0 {
             break None;
         }
 
-        if x > target_area.0 .1 {
+        // Past the target (or stalled beside it) horizontally.
+        if (x > max_x && vx >= 0) || (x < min_x && vx <= 0) {
             break None;
         }
 
@@ -56,37 +61,352 @@ fn is_good((mut vx, mut vy): &(i64, i64), target_area: &((i64, i64), (i64, i64))
     }
 }
 
-fn solve1(target_area: &((i64, i64), (i64, i64))) -> i64 {
+// Probe state: (x, y, vx, vy).
+type Step = (i64, i64, i64, i64);
+
+// Every step's (x, y, vx, vy) from launch until the probe enters the target
+// (the returned step) or can no longer reach it (None), as in `is_good`.
+fn trajectory(
+    (vx, vy): (i64, i64),
+    target_area: &((i64, i64), (i64, i64)),
+) -> (Vec<Step>, Option<usize>) {
+    let ((min_x, max_x), (min_y, _)) = *target_area;
+    let mut steps = vec![(0, 0, vx, vy)];
+    loop {
+        let (x, y, vx, vy) = steps[steps.len() - 1];
+        if is_in_target_area(x, y, target_area) {
+            let t = steps.len() - 1;
+            return (steps, Some(t));
+        }
+        if (y < min_y && vy <= 0) || (x > max_x && vx >= 0) || (x < min_x && vx <= 0) {
+            return (steps, None);
+        }
+        steps.push((x + vx, y + vy, vx - vx.signum(), vy - 1));
+    }
+}
+
+// The puzzle's picture: S is the launch point, # the probe after each step
+// and T the target area.
+fn plot(steps: &[Step], target_area: &((i64, i64), (i64, i64))) -> Vec<String> {
+    let ((min_x, max_x), (min_y, max_y)) = *target_area;
+    let xs = steps.iter().map(|s| s.0).chain([min_x, max_x]);
+    let ys = steps.iter().map(|s| s.1).chain([min_y, max_y]);
+    let (x0, x1) = (xs.clone().min().unwrap(), xs.max().unwrap());
+    let (y0, y1) = (ys.clone().min().unwrap(), ys.max().unwrap());
+    let mut grid = vec![vec!['.'; (x1 - x0 + 1) as usize]; (y1 - y0 + 1) as usize];
+    let mut put = |x: i64, y: i64, c: char| grid[(y1 - y) as usize][(x - x0) as usize] = c;
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            put(x, y, 'T');
+        }
+    }
+    steps.iter().skip(1).for_each(|s| put(s.0, s.1, '#'));
+    put(0, 0, 'S');
+    grid.iter().map(|r| r.iter().collect()).collect()
+}
+
+fn show_shot(v: (i64, i64), target_area: &((i64, i64), (i64, i64))) {
+    let (steps, entry) = trajectory(v, target_area);
+    println!("step     x     y    vx    vy");
+    for (t, (x, y, vx, vy)) in steps.iter().enumerate() {
+        println!("{:4} {:5} {:5} {:5} {:5}", t, x, y, vx, vy);
+    }
+    let (t, apex) = steps
+        .iter()
+        .enumerate()
+        .map(|(t, s)| (t, s.1))
+        .max_by_key(|&(t, y)| (y, -(t as i64)))
+        .unwrap();
+    println!("apex: y={} at step {}", apex, t);
+    match entry {
+        Some(t) => println!("enters target at step {}", t),
+        None => println!("misses target"),
+    }
+    plot(&steps, target_area)
+        .iter()
+        .for_each(|r| println!("{}", r));
+}
+
+// All good velocities with their apex, highest first; None if there are
+// infinitely many.
+fn list_velocities(target_area: &((i64, i64), (i64, i64))) -> Option<Vec<((i64, i64), i64)>> {
+    if unbounded(target_area) {
+        return None;
+    }
+    let ((x0, x1), (y0, y1)) = velocity_bounds(target_area);
+    let mut r = vec![];
+    for vx in x0..=x1 {
+        for vy in y0..=y1 {
+            if let Some(h) = is_good(&(vx, vy), target_area) {
+                r.push(((vx, vy), h));
+            }
+        }
+    }
+    r.sort_by_key(|&(v, h)| (-h, v));
+    Some(r)
+}
+
+// Whether shots of any height hit: some vx stops dead inside the target's
+// columns and the target's rows hold y = 0, which every rising shot passes
+// through again on the way down. vx = 0 covers a target holding the launch
+// point.
+fn unbounded(((min_x, max_x), (min_y, max_y)): &((i64, i64), (i64, i64))) -> bool {
+    let stalls = (*min_x.min(&0)..=*max_x.max(&0)).any(|vx| {
+        let x = x_at(vx, vx.abs());
+        *min_x <= x && x <= *max_x
+    });
+    stalls && *min_y <= 0 && 0 <= *max_y
+}
+
+// Every good velocity lies in these ranges, unless `unbounded`. A larger
+// |vx| overshoots on the first step. Above the target, a higher vy only
+// revisits its way-up heights (all above the target) on the way down;
+// below it, vy + 1 is the speed at which the probe falls past y = 0. When
+// the target spans y = 0 and no vx stalls in it, each hit comes at some
+// step t < |vx| <= reach, so vy <= max_y / t + (t - 1) / 2.
+fn velocity_bounds(
+    ((min_x, max_x), (min_y, max_y)): &((i64, i64), (i64, i64)),
+) -> ((i64, i64), (i64, i64)) {
+    let reach = min_x.abs().max(max_x.abs());
+    let vy_max = if *min_y > 0 {
+        *max_y
+    } else if *max_y < 0 {
+        -min_y
+    } else {
+        max_y + reach
+    };
+    ((*min_x.min(&0), *max_x.max(&0)), (*min_y.min(&0), vy_max))
+}
+
+// Simulates every velocity with both components in -reach..=reach; returns
+// the best height and the number of good velocities.
+fn brute_force(target_area: &((i64, i64), (i64, i64)), reach: i64) -> (i64, i64) {
     let mut best_v = 0;
-    for vx in 1..2 * target_area.0 .1 {
-        for vy in target_area.1 .0..100 {
+    let mut count = 0;
+    for vx in -reach..=reach {
+        for vy in -reach..=reach {
             if let Some(v) = is_good(&(vx, vy), target_area) {
-                if v > best_v {
-                    best_v = v;
-                }
+                best_v = best_v.max(v);
+                count += 1;
             }
         }
     }
-    best_v
+    (best_v, count)
+}
+
+// Position along one axis after t steps.
+fn x_at(vx: i64, t: i64) -> i64 {
+    let s = vx.abs().min(t);
+    vx.signum() * (vx.abs() * s - s * (s - 1) / 2)
+}
+
+fn y_at(vy: i64, t: i64) -> i64 {
+    vy * t - t * (t - 1) / 2
+}
+
+// Smallest t >= lo with pred(t), for pred monotone (false then true) on
+// [lo, hi]; hi + 1 if there is none.
+fn first_true(lo: i64, hi: i64, pred: impl Fn(i64) -> bool) -> i64 {
+    let (mut lo, mut hi) = (lo, hi + 1);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+// The vx that put the probe inside [min_x, max_x] at step t, searched in
+// [x0, x1]. For a fixed t, x only grows with vx, so they form one range.
+fn vx_range(t: i64, (min_x, max_x): (i64, i64), (x0, x1): (i64, i64)) -> Option<(i64, i64)> {
+    let lo = first_true(x0, x1, |vx| x_at(vx, t) >= min_x);
+    let hi = first_true(lo, x1, |vx| x_at(vx, t) > max_x) - 1;
+    (lo <= hi).then_some((lo, hi))
+}
+
+// The vy that put the probe inside [min_y, max_y] at step t >= 1, from
+// min_y <= vy * t - t * (t - 1) / 2 <= max_y.
+fn vy_range(t: i64, (min_y, max_y): (i64, i64)) -> (i64, i64) {
+    let drop = t * (t - 1) / 2;
+    (
+        -(-(min_y + drop)).div_euclid(t),
+        (max_y + drop).div_euclid(t),
+    )
+}
+
+// Adds [lo, hi] to a sorted list of disjoint ranges and returns how many
+// values were not covered before.
+fn cover(ranges: &mut Vec<(i64, i64)>, (lo, hi): (i64, i64)) -> i64 {
+    let mut merged = (lo, hi);
+    let mut seen = 0;
+    let mut rest = vec![];
+    for &(a, b) in ranges.iter() {
+        if b < lo - 1 || a > hi + 1 {
+            rest.push((a, b));
+        } else {
+            seen += (b.min(hi) - a.max(lo) + 1).max(0);
+            merged = (merged.0.min(a), merged.1.max(b));
+        }
+    }
+    rest.push(merged);
+    rest.sort_unstable();
+    *ranges = rest;
+    hi - lo + 1 - seen
 }
 
-fn solve2(target_area: &((i64, i64), (i64, i64))) -> i64 {
+// Best height reached before entering the target and the number of good
+// velocities, without simulating any shot; None if both are `unbounded`.
+// Each step t contributes the rectangle vx_range(t) x vy_range(t); walking
+// t upwards and merging the vx ranges per vy counts each velocity once, at
+// the step it first hits.
+fn solve(target_area: &((i64, i64), (i64, i64))) -> Option<(i64, i64)> {
+    if unbounded(target_area) {
+        return None;
+    }
+    let ((x0, x1), (y0, y1)) = velocity_bounds(target_area);
+    // Any shot in the bounds is back at y = 0 by step 2 * y1 + 1 and below
+    // the target -y0 steps later.
+    let t_max = 2 * y1 + 2 + (-y0).max(0);
+    let mut covered: Vec<Vec<(i64, i64)>> = vec![vec![]; (y1 - y0 + 1) as usize];
+    let mut best_v = 0;
     let mut count = 0;
-    for vx in 1..2 * target_area.0 .1 {
-        for vy in target_area.1 .0..200 {
-            if let Some(_) = is_good(&(vx, vy), target_area) {
-                count += 1;
+    for t in 1..=t_max {
+        let Some(xs) = vx_range(t, target_area.0, (x0, x1)) else {
+            continue;
+        };
+        let (lo, hi) = vy_range(t, target_area.1);
+        for vy in lo.max(y0)..=hi.min(y1) {
+            let new = cover(&mut covered[(vy - y0) as usize], xs);
+            if new > 0 {
+                count += new;
+                best_v = best_v.max(y_at(vy, t.min(vy.max(0))));
             }
         }
     }
-    count
+    Some((best_v, count))
+}
+
+// A random target with corners in -span..=span.
+fn random_target(rng: &mut Rng, span: i64) -> ((i64, i64), (i64, i64)) {
+    let (a, b) = (rng.range(-span, span), rng.range(-span, span));
+    let (c, d) = (rng.range(-span, span), rng.range(-span, span));
+    ((a.min(b), a.max(b)), (c.min(d), c.max(d)))
+}
+
+// Compares `solve` with `brute_force` over -reach..=reach. An unbounded
+// target must instead be hit by some shot launched at vy = 2 * reach,
+// higher than any the brute force tries.
+fn check(target_area: &((i64, i64), (i64, i64)), reach: i64) -> Result<(), String> {
+    match solve(target_area) {
+        Some(got) => {
+            let want = brute_force(target_area, reach);
+            if got == want {
+                Ok(())
+            } else {
+                Err(format!("got {:?}, want {:?}", got, want))
+            }
+        }
+        None if (-reach..=reach).any(|vx| is_good(&(vx, 2 * reach), target_area).is_some()) => {
+            Ok(())
+        }
+        None => Err("unbounded, but no high shot hits".to_string()),
+    }
+}
+
+// Runs `check` on n random targets in all quadrants.
+fn verify(n: usize) {
+    let mut rng = Rng::new(17);
+    let mut failures = 0;
+    for _ in 0..n {
+        let target = random_target(&mut rng, 60);
+        if let Err(e) = check(&target, 200) {
+            failures += 1;
+            println!("{:?}: {}", target, e);
+        }
+    }
+    println!("{} targets, {} failures", n, failures);
+}
+
+fn solve1(target_area: &((i64, i64), (i64, i64))) -> Option<i64> {
+    solve(target_area).map(|(best_v, _)| best_v)
+}
+
+fn solve2(target_area: &((i64, i64), (i64, i64))) -> Option<i64> {
+    solve(target_area).map(|(_, count)| count)
 }
 
 fn main() -> Result<(), io::Error> {
+    // --verify N: check the closed-form solver on N random targets.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|a| a == "--verify") {
+        verify(args[i + 1].parse().unwrap());
+        return Ok(());
+    }
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // --shot VX,VY: list and plot one launch; --list: every good velocity.
+    if let Some(i) = args.iter().position(|a| a == "--shot") {
+        let v: Vec<i64> = args[i + 1].split(',').map(|n| n.parse().unwrap()).collect();
+        show_shot((v[0], v[1]), &lines);
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--list") {
+        match list_velocities(&lines) {
+            Some(vs) => vs
+                .iter()
+                .for_each(|((vx, vy), h)| println!("{},{}\t{}", vx, vy, h)),
+            None => println!("{}", UNBOUNDED),
+        }
+        return Ok(());
+    }
+
+    for r in [solve1(&lines), solve2(&lines)] {
+        match r {
+            Some(n) => println!("{}", n),
+            None => println!("{}", UNBOUNDED),
+        }
+    }
     Ok(())
 }
+
+const UNBOUNDED: &str = "infinite: shots of any height reach the target";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solver_matches_brute_force() {
+        let mut rng = Rng::new(33);
+        for _ in 0..150 {
+            let target = random_target(&mut rng, 30);
+            assert_eq!(check(&target, 100), Ok(()), "{:?}", target);
+        }
+    }
+
+    #[test]
+    fn stalled_shots_are_unbounded() {
+        // vx = 3 stops at x = 6 and vx = 5 at x = 15, above a y-range
+        // holding 0.
+        for target in [((4, 14), (-5, 10)), ((15, 20), (-15, 17))] {
+            assert_eq!(solve(&target), None);
+            assert!(list_velocities(&target).is_none());
+            assert_eq!(check(&target, 100), Ok(()));
+        }
+        assert!(is_good(&(3, 1000), &((4, 14), (-5, 10))).is_some());
+    }
+
+    #[test]
+    fn slow_drift_over_wide_gap() {
+        // No vx stalls in 1276..=1325, but vx = 51 spends 49 steps getting
+        // there, long enough for vy = 24 to come back down to y = 0.
+        let target = ((1276, 1325), (-5, 5));
+        assert!(is_good(&(51, 24), &target).is_some());
+        assert_eq!(check(&target, 1400), Ok(()));
+    }
+}
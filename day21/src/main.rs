@@ -1,3 +1,5 @@
+use std::env;
+use std::fmt;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> Vec<i64> {
@@ -7,80 +9,371 @@ fn parse_input(lines: &Vec<String>) -> Vec<i64> {
         .collect()
 }
 
-fn solve1(h: &Vec<i64>) -> i64 {
-    let mut pos = h.iter().map(|x| (x - 1) as u8).collect::<Vec<u8>>();
-    let mut roll_count = 0;
-    let mut score: [u64; 2] = [0; 2];
-    let mut die_val = 0;
-    let mut next_roll = || {
-        die_val += 1;
-        roll_count += 1;
-        if die_val == 101 {
-            die_val = 1;
+// A deterministic die for the practice game.
+trait Die {
+    fn roll(&mut self) -> u64;
+}
+
+// Rolls 1, 2, ..., sides, 1, 2, ...
+struct Wrapping {
+    sides: u64,
+    last: u64,
+}
+
+impl Die for Wrapping {
+    fn roll(&mut self) -> u64 {
+        self.last = self.last % self.sides + 1;
+        self.last
+    }
+}
+
+// Repeats a user-supplied sequence of rolls.
+struct Cycle {
+    values: Vec<u64>,
+    next: usize,
+}
+
+impl Die for Cycle {
+    fn roll(&mut self) -> u64 {
+        let v = self.values[self.next];
+        self.next = (self.next + 1) % self.values.len();
+        v
+    }
+}
+
+// Parses "d100" or a cycle such as "6,1,3".
+fn parse_die(s: &str) -> Box<dyn Die> {
+    match s.strip_prefix('d') {
+        Some(sides) => Box::new(Wrapping {
+            sides: sides.parse().unwrap(),
+            last: 0,
+        }),
+        None => Box::new(Cycle {
+            values: s.split(',').map(|x| x.parse().unwrap()).collect(),
+            next: 0,
+        }),
+    }
+}
+
+// One turn of the practice game; `pos` is 1-based.
+struct Turn {
+    player: usize,
+    rolls: Vec<u64>,
+    pos: u64,
+    score: u64,
+}
+
+// Plays until someone reaches `target`, using the board size and rolls per
+// turn from `game`. Returns every turn and the final scores.
+fn simulate(h: &[i64], game: &Game, die: &mut dyn Die, target: u64) -> (Vec<Turn>, Vec<u64>) {
+    let mut pos = h.iter().map(|x| (x - 1) as u64).collect::<Vec<u64>>();
+    let mut score = vec![0; pos.len()];
+    let mut turns = vec![];
+    let mut i = 0;
+    while score.iter().all(|s| *s < target) {
+        let rolls = (0..game.rolls).map(|_| die.roll()).collect::<Vec<u64>>();
+        pos[i] = (pos[i] + rolls.iter().sum::<u64>()) % game.board;
+        score[i] += pos[i] + 1;
+        turns.push(Turn {
+            player: i,
+            rolls,
+            pos: pos[i] + 1,
+            score: score[i],
+        });
+        i = (i + 1) % pos.len();
+    }
+    (turns, score)
+}
+
+fn log_csv(turns: &[Turn]) {
+    println!("turn,player,rolls,position,score");
+    for (t, turn) in turns.iter().enumerate() {
+        let rolls = turn.rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        println!(
+            "{},{},{},{},{}",
+            t + 1,
+            turn.player + 1,
+            rolls.join("+"),
+            turn.pos,
+            turn.score
+        );
+    }
+}
+
+fn log_json(turns: &[Turn]) {
+    println!("[");
+    for (t, turn) in turns.iter().enumerate() {
+        let rolls = turn.rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        println!(
+            "  {{\"turn\": {}, \"player\": {}, \"rolls\": [{}], \"position\": {}, \"score\": {}}}{}",
+            t + 1,
+            turn.player + 1,
+            rolls.join(", "),
+            turn.pos,
+            turn.score,
+            if t + 1 < turns.len() { "," } else { "" }
+        );
+    }
+    println!("]");
+}
+
+fn solve1(h: &[i64], game: &Game, die: &mut dyn Die) -> i64 {
+    let (turns, score) = simulate(h, game, die, 1000);
+    let roll_count = turns.len() as u64 * game.rolls as u64;
+    (score.iter().min().unwrap() * roll_count) as i64
+}
+
+// Unsigned big integer, base 2^32 limbs, least significant first, no
+// trailing zero limbs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Big(Vec<u32>);
+
+impl Big {
+    fn from_u64(mut n: u64) -> Big {
+        let mut v = vec![];
+        while n > 0 {
+            v.push(n as u32);
+            n >>= 32;
         }
-        die_val
-    };
-    while score[0] < 1000 && score[1] < 1000 {
-        for i in 0..2 {
-            let die_sum: u64 = (0..3).into_iter().map(|_| next_roll()).sum::<u64>() % 10;
-            pos[i] = (pos[i] + (die_sum as u8)) % 10;
-            score[i] += (pos[i] + 1) as u64;
-            if score[i] >= 1000 {
-                break;
+        Big(v)
+    }
+
+    fn add(&mut self, other: &Big) {
+        let mut carry = 0u64;
+        for i in 0..self.0.len().max(other.0.len()) {
+            if i == self.0.len() {
+                self.0.push(0);
+            }
+            let s = self.0[i] as u64 + *other.0.get(i).unwrap_or(&0) as u64 + carry;
+            self.0[i] = s as u32;
+            carry = s >> 32;
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    // self += a * b, without building the product.
+    fn add_mul(&mut self, a: &Big, b: &Big) {
+        for (i, &x) in a.0.iter().enumerate() {
+            let mut carry = 0u64;
+            let mut j = 0;
+            while j < b.0.len() || carry > 0 {
+                if i + j == self.0.len() {
+                    self.0.push(0);
+                }
+                let y = *b.0.get(j).unwrap_or(&0) as u64;
+                let t = self.0[i + j] as u64 + x as u64 * y + carry;
+                self.0[i + j] = t as u32;
+                carry = t >> 32;
+                j += 1;
             }
         }
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+}
+
+impl Ord for Big {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
     }
-    (score[..].iter().find(|x| **x < 1000).unwrap() * roll_count) as i64
-}
-
-fn doit(pos: [u8; 2], s: [u64; 2], rolls: &mut Vec<usize>, die_sums: &[u64; 10]) -> (u64, u64) {
-    let turn = rolls.len() % 2;
-    let mut npos = pos.clone();
-    let mut ns = s.clone();
-    let mut wins = [0; 2];
-    // println!("{:?} {:?} {:?}", pos, s, rolls);
-    for roll in 3usize..10 {
-        npos[turn] = (pos[turn] + (roll as u8)) % 10;
-        ns[turn] = s[turn] + (npos[turn] as u64 + 1);
-        if ns[turn] >= 21 {
-            // println!("WIN: {:?} {:?} {:?} {}", npos, ns, rolls, roll);
-            let ways = rolls.iter().map(|x| die_sums[*x]).product::<u64>() * die_sums[roll];
-            wins[turn] += ways;
-        } else {
-            rolls.push(roll);
-            let (w1, w2) = doit(npos, ns, rolls, die_sums);
-            rolls.pop();
-            wins[0] += w1;
-            wins[1] += w2;
+}
+
+impl PartialOrd for Big {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Big {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Peel off base 10^9 digits, least significant first.
+        let mut n = self.0.clone();
+        let mut parts = vec![];
+        while !n.is_empty() {
+            let mut rem = 0u64;
+            for limb in n.iter_mut().rev() {
+                let cur = (rem << 32) | *limb as u64;
+                *limb = (cur / 1_000_000_000) as u32;
+                rem = cur % 1_000_000_000;
+            }
+            while n.last() == Some(&0) {
+                n.pop();
+            }
+            parts.push(rem);
+        }
+        match parts.split_last() {
+            None => write!(f, "0"),
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                rest.iter().rev().try_for_each(|p| write!(f, "{:09}", p))
+            }
         }
     }
-    (wins[0], wins[1])
 }
 
-fn solve2(h: &Vec<i64>) -> u64 {
-    let pos = h.iter().map(|x| (x - 1) as u8).collect::<Vec<u8>>();
-    let mut die_sums = [0; 10];
-    for i in 1..4 {
-        for j in 1..4 {
-            for k in 1..4 {
-                die_sums[i + j + k] += 1;
+// Rules for the Dirac die game: a circular board numbered 1..=board, a die
+// with faces 1..=faces rolled `rolls` times per turn, and the score to win.
+struct Game {
+    board: u64,
+    faces: u64,
+    rolls: u32,
+    target: u64,
+}
+
+impl Game {
+    // Number of universes for each sum of one turn's rolls.
+    fn sums(&self) -> Vec<(u64, Big)> {
+        let mut dist = vec![Big::from_u64(1)];
+        for _ in 0..self.rolls {
+            let mut next = vec![Big::default(); dist.len() + self.faces as usize];
+            for (s, ways) in dist.iter().enumerate() {
+                for face in 1..=self.faces as usize {
+                    next[s + face].add(ways);
+                }
             }
+            dist = next;
         }
+        dist.into_iter()
+            .enumerate()
+            .filter(|(_, w)| !w.0.is_empty())
+            .map(|(s, w)| (s as u64, w))
+            .collect()
     }
+}
 
-    let (w1, w2) = doit([pos[0], pos[1]], [0, 0], &mut vec![], &die_sums);
-    if w1 > w2 {
-        w1
-    } else {
-        w2
+// Memoised count of universes each player wins from a state: each
+// player's 0-based position and score, and whose turn it is. States are
+// rotated so the player to move comes first, which makes the turn implicit;
+// the rest packs into one integer that indexes `memo` directly, and each
+// state's counts are stored once in `wins`, in the same rotated order.
+struct Dirac<'a> {
+    game: &'a Game,
+    sums: Vec<(u64, Big)>,
+    // Packed state -> offset of its counts in `wins`, or NONE.
+    memo: Vec<usize>,
+    wins: Vec<Big>,
+}
+
+const NONE: usize = usize::MAX;
+
+impl<'a> Dirac<'a> {
+    fn new(game: &'a Game, players: usize) -> Self {
+        let states = (game.board * game.target)
+            .checked_pow(players as u32)
+            .filter(|&n| n <= 1 << 30)
+            .expect("too many game states to index") as usize;
+        Dirac {
+            game,
+            sums: game.sums(),
+            memo: vec![NONE; states],
+            wins: vec![],
+        }
+    }
+
+    fn key(&self, state: &[(u64, u64)]) -> usize {
+        let cell = self.game.board * self.game.target;
+        state.iter().fold(0, |k, &(pos, score)| {
+            k * cell + pos * self.game.target + score
+        }) as usize
     }
+
+    // Offset in `wins` of the counts from `state`, with state[0] to move.
+    // `state` is left as found.
+    fn count(&mut self, state: &mut [(u64, u64)]) -> usize {
+        let key = self.key(state);
+        if self.memo[key] != NONE {
+            return self.memo[key];
+        }
+        let n = state.len();
+        let mut wins = vec![Big::default(); n];
+        let (pos, score) = state[0];
+        for i in 0..self.sums.len() {
+            let npos = (pos + self.sums[i].0) % self.game.board;
+            let nscore = score + npos + 1;
+            if nscore >= self.game.target {
+                wins[0].add(&self.sums[i].1);
+                continue;
+            }
+            // The next player moves from the front; they were player 1.
+            state[0] = (npos, nscore);
+            state.rotate_left(1);
+            let at = self.count(state);
+            state.rotate_right(1);
+            state[0] = (pos, score);
+            for k in 0..n {
+                wins[(k + 1) % n].add_mul(&self.sums[i].1, &self.wins[at + k]);
+            }
+        }
+        let at = self.wins.len();
+        self.wins.extend(wins);
+        self.memo[key] = at;
+        at
+    }
+}
+
+fn dirac_wins(h: &[i64], game: &Game) -> Vec<Big> {
+    // A start past the end of a small board only matters modulo its size.
+    let mut state = h
+        .iter()
+        .map(|x| ((x - 1) as u64 % game.board, 0))
+        .collect::<Vec<_>>();
+    let mut d = Dirac::new(game, state.len());
+    let at = d.count(&mut state);
+    d.wins.drain(at..at + h.len()).collect()
+}
+
+fn solve2(h: &[i64], game: &Game) -> Big {
+    dirac_wins(h, game).into_iter().max().unwrap()
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // --board N --faces N --rolls N --target N change the Dirac game;
+    // --wins prints every player's win count instead of the largest.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |f: &str, default: u64| {
+        args.iter()
+            .position(|a| a == f)
+            .map_or(default, |i| args[i + 1].parse().unwrap())
+    };
+    let game = Game {
+        board: arg("--board", 10),
+        faces: arg("--faces", 3),
+        rolls: arg("--rolls", 3) as u32,
+        target: arg("--target", 21),
+    };
+
+    // --die d100 | --die 6,1,3 picks the practice die; --log csv|json prints
+    // the practice game turn by turn.
+    let die_spec = args
+        .iter()
+        .position(|a| a == "--die")
+        .map_or("d100", |i| &args[i + 1]);
+    if let Some(i) = args.iter().position(|a| a == "--log") {
+        let (turns, _) = simulate(&lines, &game, &mut *parse_die(die_spec), 1000);
+        match args[i + 1].as_str() {
+            "json" => log_json(&turns),
+            _ => log_csv(&turns),
+        }
+        return Ok(());
+    }
+
+    println!("{}", solve1(&lines, &game, &mut *parse_die(die_spec)));
+    if args.iter().any(|a| a == "--wins") {
+        for (i, w) in dirac_wins(&lines, &game).iter().enumerate() {
+            println!("player {}: {}", i + 1, w);
+        }
+    } else {
+        println!("{}", solve2(&lines, &game));
+    }
     Ok(())
 }
@@ -1,8 +1,5 @@
-use std::cmp::Ordering;
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
-use std::collections::HashMap;
-use std::collections::HashSet;
+use search::Graph;
+use std::env;
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> M {
@@ -16,6 +13,75 @@ fn parse_input(lines: &Vec<String>) -> M {
 
 type M = [[char; 13]; 7];
 
+// Compact form of `M`: only the 11 hallway cells and up to 4x4 room cells
+// are stored, 3 bits each ('.' = 0, 'A'..'D' = 1..4), with the room depth in
+// the top bits so that the display form can be rebuilt.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct Packed(u128);
+
+const HALL: usize = 11;
+const CELL_BITS: usize = 3;
+const DEPTH_SHIFT: usize = 120;
+
+fn room_depth(m: &M) -> usize {
+    (2..6)
+        .take_while(|&i| is_pod(m[i][3]) || m[i][3] == '.')
+        .count()
+}
+
+fn cell_code(c: char) -> u128 {
+    match c {
+        '.' => 0,
+        'A' => 1,
+        'B' => 2,
+        'C' => 3,
+        'D' => 4,
+        _ => panic!("not a burrow cell: {:?}", c),
+    }
+}
+
+fn code_cell(v: u128) -> char {
+    ['.', 'A', 'B', 'C', 'D'][v as usize]
+}
+
+// (row, col) of every packed cell for a burrow with the given room depth.
+fn cells(depth: usize) -> impl Iterator<Item = (usize, usize)> {
+    (1..=HALL)
+        .map(|j| (1, j))
+        .chain((2..2 + depth).flat_map(|i| (3..10).step_by(2).map(move |j| (i, j))))
+}
+
+impl Packed {
+    fn pack(m: &M) -> Packed {
+        let depth = room_depth(m);
+        let mut v = (depth as u128) << DEPTH_SHIFT;
+        for (k, (i, j)) in cells(depth).enumerate() {
+            v |= cell_code(m[i][j]) << (k * CELL_BITS);
+        }
+        Packed(v)
+    }
+
+    fn depth(&self) -> usize {
+        (self.0 >> DEPTH_SHIFT) as usize
+    }
+
+    fn unpack(&self) -> M {
+        let depth = self.depth();
+        let mut rows = vec![
+            "#############".to_string(),
+            "#...........#".to_string(),
+            "###.#.#.#.###".to_string(),
+        ];
+        rows.extend((1..depth).map(|_| "  #.#.#.#.#".to_string()));
+        rows.push("  #########".to_string());
+        let mut m = parse_input(&rows);
+        for (k, (i, j)) in cells(depth).enumerate() {
+            m[i][j] = code_cell((self.0 >> (k * CELL_BITS)) & 0b111);
+        }
+        m
+    }
+}
+
 fn d(m: &M) {
     for i in 0..m.len() {
         let v = m[i][..].iter().collect::<String>();
@@ -160,90 +226,102 @@ fn valid_moves(m: M) -> Vec<(M, i64)> {
     moves
 }
 
-#[derive(Eq, PartialEq)]
-struct HeapEntry {
-    m: M,
-    d: i64,
+// The search space: burrow states linked by single amphipod moves.
+struct Burrow;
+
+impl Graph for Burrow {
+    type State = Packed;
+
+    fn neighbors(&self, s: &Packed) -> impl Iterator<Item = (Packed, i64)> {
+        valid_moves(s.unpack())
+            .into_iter()
+            .map(|(m, c)| (Packed::pack(&m), c))
+    }
 }
 
-impl Ord for HeapEntry {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.d.cmp(&other.d)
+// Lower bound on the energy still needed: every pod that is not settled
+// must at least walk to its room ignoring all other pods. A pod in its own
+// room with a stranger below it must step out and come back in.
+fn heuristic(m: &M) -> i64 {
+    let mut h = 0;
+    for (j, &c) in m[1].iter().enumerate() {
+        if is_pod(c) {
+            h += ((j as i64 - dest(c) as i64).abs() + 1) * cost(c);
+        }
+    }
+    for j in (3..10).step_by(2) {
+        for i in 2..6 {
+            let c = m[i][j];
+            if !is_pod(c) {
+                continue;
+            }
+            let steps = if dest(c) != j {
+                (i as i64 - 1) + (j as i64 - dest(c) as i64).abs() + 1
+            } else if (i + 1..6).any(|k| is_pod(m[k][j]) && m[k][j] != c) {
+                (i as i64 - 1) + 3
+            } else {
+                0
+            };
+            h += steps * cost(c);
+        }
     }
+    h
 }
-impl PartialOrd for HeapEntry {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+
+struct Solution {
+    cost: i64,
+    // States from start to final, each with the cost of the move into it.
+    moves: Vec<(M, i64)>,
+    expanded: usize,
+}
+
+fn solve(start: &M, final_state: &M, astar: bool) -> Solution {
+    let final_p = Packed::pack(final_state);
+    let is_final = |p: &Packed| *p == final_p;
+    let r = if astar {
+        search::astar(&Burrow, Packed::pack(start), is_final, |p| {
+            heuristic(&p.unpack())
+        })
+    } else {
+        search::dijkstra(&Burrow, Packed::pack(start), is_final)
+    };
+
+    let path = r.path().unwrap();
+    let moves = path
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let c = if i == 0 {
+                0
+            } else {
+                r.dist(p).unwrap() - r.dist(&path[i - 1]).unwrap()
+            };
+            (p.unpack(), c)
+        })
+        .collect();
+    Solution {
+        cost: r.cost().unwrap(),
+        moves,
+        expanded: r.expanded,
     }
 }
 
-fn solve(start: &M, final_state: &M) -> i64 {
-    let mut dist: HashMap<M, i64> = HashMap::new();
-    let mut found: HashSet<M> = HashSet::new();
-    let mut heap = BinaryHeap::new();
-    let mut prev: HashMap<M, M> = HashMap::new();
-    heap.push(Reverse(HeapEntry {
-        m: start.clone(),
-        d: 0,
-    }));
-    dist.insert(start.clone(), 0);
-    while !heap.is_empty() {
-        let Reverse(v) = heap.pop().unwrap();
-        if !found.insert(v.m) {
-            continue;
-        }
-        if v.m == *final_state {
-            // res = v.d;
-            break;
-        }
-        let moves = valid_moves(v.m);
-        // d(&v.m);
-        // if v.m == test_state {
-        //     println!("Found moves for:");
-        //     d(&v.m);
-        //     println!("=>");
-        //     moves.iter().for_each(|x| {
-        //         println!("cost: {}", x.1);
-        //         d(&x.0);
-        //     });
-        //     println!("====");
-        // }
-        for (mv, move_cost) in moves.iter() {
-            let nbr_cost = v.d + *move_cost;
-            let is_better = dist.get(mv).map_or(true, |&curr| nbr_cost < curr);
-            if is_better {
-                dist.insert(*mv, nbr_cost);
-                prev.insert(*mv, v.m);
-                heap.push(Reverse(HeapEntry {
-                    m: *mv,
-                    d: nbr_cost,
-                }));
+fn report(s: &Solution, opts: &Opts) -> i64 {
+    if opts.show_moves {
+        for (i, (m, c)) in s.moves.iter().enumerate() {
+            if i > 0 {
+                println!("=> ({})", c);
             }
+            d(m);
         }
     }
-    // let mut move_list = vec![test_state];
-    // let mut costs = vec![];
-    // let mut cost = *dist.get(&test_state).unwrap();
-    // let mut node = test_state;
-    // println!("From:");
-    // d(&node);
-    // while let Some(p) = prev.get(&node) {
-    //     let p_cost = *dist.get(p).unwrap();
-    //     let move_cost = cost - p_cost;
-    //     cost = p_cost;
-    //     costs.push(move_cost);
-    //     move_list.push(*p);
-    //     // println!("From:");
-    //     // d(&node);
-    //     println!("=> ({})", move_cost);
-    //     d(p);
-    //     node = *p;
-    // }
-
-    *dist.get(final_state).unwrap()
+    if opts.stats {
+        eprintln!("expanded {} states", s.expanded);
+    }
+    s.cost
 }
 
-fn solve1(h: &M) -> i64 {
+fn solve1(h: &M, opts: &Opts) -> i64 {
     let final_state: Vec<String> = vec![
         "#############",
         "#...........#",
@@ -255,10 +333,10 @@ fn solve1(h: &M) -> i64 {
     .map(|x| x.to_string())
     .collect();
     let final_state = parse_input(&final_state);
-    solve(h, &final_state)
+    report(&solve(h, &final_state, opts.astar), opts)
 }
 
-fn solve2(h: &M) -> i64 {
+fn solve2(h: &M, opts: &Opts) -> i64 {
     let final_state: Vec<String> = vec![
         "#############",
         "#...........#",
@@ -288,14 +366,33 @@ fn solve2(h: &M) -> i64 {
         start[i + 3][..r.len()].clone_from_slice(r);
     });
 
-    solve(&start, &final_state)
+    report(&solve(&start, &final_state, opts.astar), opts)
+}
+
+struct Opts {
+    astar: bool,
+    show_moves: bool,
+    stats: bool,
+}
+
+impl Opts {
+    fn from_args() -> Opts {
+        let args: Vec<String> = env::args().skip(1).collect();
+        let has = |f: &str| args.iter().any(|a| a == f);
+        Opts {
+            astar: has("--astar"),
+            show_moves: has("--show-moves"),
+            stats: has("--stats"),
+        }
+    }
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+    let opts = Opts::from_args();
+    println!("{}", solve1(&lines, &opts));
+    println!("{}", solve2(&lines, &opts));
     Ok(())
 }
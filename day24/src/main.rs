@@ -1,4 +1,6 @@
-use std::collections::HashSet;
+use rng::Rng;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
 use std::io::{self, BufRead};
 
 #[derive(Debug, Clone)]
@@ -22,6 +24,15 @@ enum Var {
 }
 
 impl Var {
+    fn idx(&self) -> usize {
+        match self {
+            Var::W => 0,
+            Var::X => 1,
+            Var::Y => 2,
+            Var::Z => 3,
+        }
+    }
+
     fn parse(s: &str) -> Self {
         match s {
             "w" => Var::W,
@@ -344,14 +355,97 @@ impl Term {
     }
 }
 
+// Renders terms as infix expressions. Compound subterms that occur more than
+// once (across all rendered roots) are emitted once as `let tN = ...;`.
+#[derive(Default)]
+struct Printer {
+    counts: BTreeMap<Term, usize>,
+    names: BTreeMap<Term, String>,
+    lets: Vec<String>,
+}
+
+impl Printer {
+    fn children(t: &Term) -> Vec<&Term> {
+        match t {
+            Term::L(_) | Term::I(_) | Term::E(_) => vec![],
+            Term::Add(v) | Term::Mul(v) => v.iter().map(|x| &**x).collect(),
+            Term::Div(a, b) | Term::Mod(a, b) | Term::Eql(a, b) => vec![&**a, &**b],
+        }
+    }
+
+    // Must be called on every root before `emit`.
+    fn count(&mut self, t: &Term) {
+        if Printer::children(t).is_empty() {
+            return;
+        }
+        let n = self.counts.entry(t.clone()).or_insert(0);
+        *n += 1;
+        if *n == 1 {
+            Printer::children(t).into_iter().for_each(|c| self.count(c));
+        }
+    }
+
+    fn emit(&mut self, t: &Term) -> String {
+        if let Some(name) = self.names.get(t) {
+            return name.clone();
+        }
+        let mut arg = |c: &Term| {
+            let s = self.emit(c);
+            if matches!(
+                c,
+                Term::Add(_) | Term::Mul(_) | Term::Div(_, _) | Term::Mod(_, _)
+            ) && !self.names.contains_key(c)
+            {
+                format!("({})", s)
+            } else {
+                s
+            }
+        };
+        let s = match t {
+            Term::L(n) => return n.to_string(),
+            Term::I(k) => return format!("in{}", k),
+            Term::E(k) => return format!("e{}", k),
+            Term::Add(v) => v.iter().map(|x| arg(x)).collect::<Vec<_>>().join(" + "),
+            Term::Mul(v) => v.iter().map(|x| arg(x)).collect::<Vec<_>>().join(" * "),
+            Term::Div(a, b) => format!("{} / {}", arg(a), arg(b)),
+            Term::Mod(a, b) => format!("{} % {}", arg(a), arg(b)),
+            Term::Eql(a, b) => format!("[{} == {}]", arg(a), arg(b)),
+        };
+        if self.counts.get(t).is_some_and(|&n| n > 1) {
+            let name = format!("t{}", self.names.len());
+            self.lets.push(format!("let {} = {};", name, s));
+            self.names.insert(t.clone(), name.clone());
+            name
+        } else {
+            s
+        }
+    }
+}
+
 impl ALU {
     fn print(&self) {
         // println!("ALU: input={}", self.input.len());
+        let mut p = Printer::default();
+        let roots =
+            self.double_eq
+                .iter()
+                .chain([&self.w_term, &self.x_term, &self.y_term, &self.z_term]);
+        roots.clone().for_each(|t| p.count(t));
+        let mut out = vec![];
+        for (k, t) in self.double_eq.iter().enumerate() {
+            out.push(format!("e{} = {}", k, p.emit(t)));
+        }
+        for (n, t) in ["w", "x", "y", "z"]
+            .iter()
+            .zip(roots.skip(self.double_eq.len()))
+        {
+            out.push(format!("{} = {}", n, p.emit(t)));
+        }
         println!("ALU:");
-        println!("w_term: {:?}", self.w_term);
-        println!("x_term: {:?}", self.x_term);
-        println!("y_term: {:?}", self.y_term);
-        println!("z_term: {:?}", self.z_term);
+        p.lets
+            .iter()
+            .chain(out.iter())
+            .for_each(|l| println!("{}", l));
     }
 
     fn new() -> Self {
@@ -474,6 +568,704 @@ fn parse_input(lines: &Vec<String>) -> Vec<Cmd> {
         .collect()
 }
 
+// Concrete register file: w, x, y, z (indexed by `Var::idx`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct Regs([i64; 4]);
+
+impl Regs {
+    fn get(&self, v: Var) -> i64 {
+        self.0[v.idx()]
+    }
+
+    fn val(&self, v: &Val) -> i64 {
+        match v {
+            Val::L(n) => *n,
+            Val::V(var) => self.get(*var),
+        }
+    }
+}
+
+impl std::fmt::Display for Regs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let [w, x, y, z] = self.0;
+        write!(f, "w={} x={} y={} z={}", w, x, y, z)
+    }
+}
+
+// Reasons the ALU refuses to continue; each carries the offending pc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecError {
+    InputExhausted(usize),
+    DivByZero(usize),
+    BadModulus(usize),
+    NegativeMod(usize),
+    Overflow(usize),
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExecError::InputExhausted(pc) => write!(f, "pc {}: inp with no input left", pc),
+            ExecError::DivByZero(pc) => write!(f, "pc {}: div by zero", pc),
+            ExecError::BadModulus(pc) => write!(f, "pc {}: mod by zero or negative", pc),
+            ExecError::NegativeMod(pc) => write!(f, "pc {}: mod of negative value", pc),
+            ExecError::Overflow(pc) => write!(f, "pc {}: arithmetic overflow", pc),
+        }
+    }
+}
+
+fn apply(op: Op, a: i64, b: i64, pc: usize) -> Result<i64, ExecError> {
+    match op {
+        Op::Add => a.checked_add(b).ok_or(ExecError::Overflow(pc)),
+        Op::Mul => a.checked_mul(b).ok_or(ExecError::Overflow(pc)),
+        Op::Div => {
+            if b == 0 {
+                return Err(ExecError::DivByZero(pc));
+            }
+            // Only i64::MIN / -1 overflows.
+            a.checked_div(b).ok_or(ExecError::Overflow(pc))
+        }
+        Op::Mod => {
+            if b <= 0 {
+                return Err(ExecError::BadModulus(pc));
+            }
+            if a < 0 {
+                return Err(ExecError::NegativeMod(pc));
+            }
+            Ok(a % b)
+        }
+        Op::Eql => Ok((a == b) as i64),
+    }
+}
+
+// Step-wise executor for a program on a fixed input sequence.
+struct Machine<'a> {
+    prog: &'a [Cmd],
+    input: &'a [i64],
+    pc: usize,
+    next_input: usize,
+    regs: Regs,
+}
+
+impl<'a> Machine<'a> {
+    fn new(prog: &'a [Cmd], input: &'a [i64]) -> Self {
+        Machine {
+            prog,
+            input,
+            pc: 0,
+            next_input: 0,
+            regs: Regs::default(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pc >= self.prog.len()
+    }
+
+    // Executes one instruction.
+    fn step(&mut self) -> Result<(), ExecError> {
+        let (var, v) = match self.prog[self.pc] {
+            Cmd::Inp(var) => {
+                let v = *self
+                    .input
+                    .get(self.next_input)
+                    .ok_or(ExecError::InputExhausted(self.pc))?;
+                self.next_input += 1;
+                (var, v)
+            }
+            Cmd::Cmd(op, var, val) => (
+                var,
+                apply(op, self.regs.get(var), self.regs.val(&val), self.pc)?,
+            ),
+        };
+        self.regs.0[var.idx()] = v;
+        self.pc += 1;
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<Regs, ExecError> {
+        while !self.done() {
+            self.step()?;
+        }
+        Ok(self.regs)
+    }
+}
+
+fn run(prog: &[Cmd], input: &[i64]) -> Result<Regs, ExecError> {
+    Machine::new(prog, input).run()
+}
+
+// Operand of compiled code: a register index or a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    R(usize),
+    K(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Ins {
+    Inp(usize),
+    Set(usize, Arg),
+    Op(Op, usize, Arg),
+}
+
+// Flat bytecode for a program. Each instruction keeps the pc of the command
+// it came from so that errors match the interpreter's.
+struct Compiled {
+    code: Vec<(usize, Ins)>,
+}
+
+// True if `op` can never fail at run time given a right operand that is
+// the constant `k` (None for a register). With a register operand only
+// `eql` is safe: anything else may overflow or fault.
+fn op_is_safe(op: Op, k: Option<i64>) -> bool {
+    match (op, k) {
+        (Op::Eql, _) | (Op::Add, Some(0)) | (Op::Mul, Some(0 | 1)) => true,
+        (Op::Div, Some(k)) => k != 0 && k != -1,
+        _ => false,
+    }
+}
+
+// True if the instruction can never fail at run time.
+fn is_safe(ins: &Ins) -> bool {
+    match ins {
+        Ins::Inp(_) => false,
+        Ins::Set(..) => true,
+        Ins::Op(op, _, Arg::K(k)) => op_is_safe(*op, Some(*k)),
+        Ins::Op(op, _, Arg::R(_)) => op_is_safe(*op, None),
+    }
+}
+
+// Compiles `prog`, folding constants, turning `mul r 0` + `add r a` into a
+// plain set, and dropping writes to registers that are not read before being
+// overwritten. Only the registers in `live_out` are guaranteed to be correct
+// at the end.
+fn compile(prog: &[Cmd], live_out: [bool; 4]) -> Compiled {
+    // Forward pass: constant propagation and algebraic simplification.
+    let mut known: [Option<i64>; 4] = [Some(0); 4];
+    let mut code = vec![];
+    for (pc, c) in prog.iter().enumerate() {
+        let (op, d, val) = match c {
+            Cmd::Inp(v) => {
+                known[v.idx()] = None;
+                code.push((pc, Ins::Inp(v.idx())));
+                continue;
+            }
+            Cmd::Cmd(op, v, val) => (*op, v.idx(), val),
+        };
+        let b = match val {
+            Val::L(n) => Arg::K(*n),
+            Val::V(u) => known[u.idx()].map_or(Arg::R(u.idx()), Arg::K),
+        };
+        let ins = match (op, known[d], b) {
+            (_, Some(x), Arg::K(y)) => match apply(op, x, y, pc) {
+                Ok(r) => Ins::Set(d, Arg::K(r)),
+                Err(_) => Ins::Op(op, d, b),
+            },
+            (Op::Mul, _, Arg::K(0)) | (Op::Mul, Some(0), _) => Ins::Set(d, Arg::K(0)),
+            (Op::Add, Some(0), _) => Ins::Set(d, b),
+            (Op::Add, _, Arg::K(0)) | (Op::Mul, _, Arg::K(1)) | (Op::Div, _, Arg::K(1)) => continue,
+            _ => Ins::Op(op, d, b),
+        };
+        known[d] = match ins {
+            Ins::Set(_, Arg::K(k)) => Some(k),
+            _ => None,
+        };
+        code.push((pc, ins));
+    }
+
+    // Backward pass: drop side-effect free writes that are never read.
+    let mut live = live_out;
+    let mut out = vec![];
+    for (pc, ins) in code.into_iter().rev() {
+        let (d, reads_d, arg) = match ins {
+            Ins::Inp(d) => (d, false, None),
+            Ins::Set(d, a) => (d, false, Some(a)),
+            Ins::Op(_, d, a) => (d, true, Some(a)),
+        };
+        if !live[d] && is_safe(&ins) {
+            continue;
+        }
+        if let Ins::Set(_, Arg::R(r)) = ins {
+            if r == d {
+                continue;
+            }
+        }
+        live[d] = reads_d;
+        if let Some(Arg::R(r)) = arg {
+            live[r] = true;
+        }
+        out.push((pc, ins));
+    }
+    out.reverse();
+    Compiled { code: out }
+}
+
+impl Compiled {
+    fn run(&self, input: &[i64]) -> Result<Regs, ExecError> {
+        let mut r = [0i64; 4];
+        let mut next_input = 0;
+        for &(pc, ins) in self.code.iter() {
+            let get = |a: Arg, r: &[i64; 4]| match a {
+                Arg::R(i) => r[i],
+                Arg::K(k) => k,
+            };
+            match ins {
+                Ins::Inp(d) => {
+                    r[d] = *input.get(next_input).ok_or(ExecError::InputExhausted(pc))?;
+                    next_input += 1;
+                }
+                Ins::Set(d, a) => r[d] = get(a, &r),
+                Ins::Op(op, d, a) => r[d] = apply(op, r[d], get(a, &r), pc)?,
+            }
+        }
+        Ok(Regs(r))
+    }
+}
+
+// Whether the interpreter and both compilations of `prog` agree on `input`:
+// all registers (or the same error) for the fully-live compilation, z only
+// for the z-live one.
+fn compiled_agrees(prog: &[Cmd], full: &Compiled, z_only: &Compiled, input: &[i64]) -> bool {
+    let want = run(prog, input);
+    let z = |r: &Result<Regs, ExecError>| r.map(|r| r.get(Var::Z));
+    full.run(input) == want && z(&z_only.run(input)) == z(&want)
+}
+
+// Runs the interpreter and the compiled program on `n` random inputs and
+// reports mismatches, as `compiled_agrees`.
+fn check_compile(prog: &[Cmd], n: usize) {
+    let full = compile(prog, [true; 4]);
+    let z_only = compile(prog, [false, false, false, true]);
+    println!(
+        "instructions: {} -> {} (all regs), {} (z only)",
+        prog.len(),
+        full.code.len(),
+        z_only.code.len()
+    );
+    let inputs = prog.iter().filter(|c| matches!(c, Cmd::Inp(_))).count();
+    let mut rng = Rng::new(0x2021_2024);
+    let mut mismatches = 0;
+    let mut accepted = 0;
+    for _ in 0..n {
+        let input: Vec<i64> = (0..inputs).map(|_| rng.range(1, 9)).collect();
+        if !compiled_agrees(prog, &full, &z_only, &input) {
+            mismatches += 1;
+            println!("mismatch on {:?}", input);
+        }
+        if run(prog, &input).map(|r| r.get(Var::Z)) == Ok(0) {
+            accepted += 1;
+        }
+    }
+    println!(
+        "{} inputs, {} mismatches, {} accepted",
+        n, mismatches, accepted
+    );
+}
+
+fn fmt_cmd(c: &Cmd) -> String {
+    let var = |v: &Var| format!("{:?}", v).to_lowercase();
+    match c {
+        Cmd::Inp(v) => format!("inp {}", var(v)),
+        Cmd::Cmd(op, v, val) => {
+            let arg = match val {
+                Val::L(n) => n.to_string(),
+                Val::V(a) => var(a),
+            };
+            format!("{} {} {}", format!("{:?}", op).to_lowercase(), var(v), arg)
+        }
+    }
+}
+
+// Runs the program printing the registers after every instruction when
+// `trace` is set, or else only when stopping at one of `breakpoints` (pcs).
+fn debug(prog: &[Cmd], input: &[i64], trace: bool, breakpoints: &HashSet<usize>) {
+    let mut m = Machine::new(prog, input);
+    while !m.done() {
+        if breakpoints.contains(&m.pc) {
+            println!("break at pc {}: {}", m.pc, m.regs);
+        }
+        let pc = m.pc;
+        if let Err(e) = m.step() {
+            println!("error: {}", e);
+            return;
+        }
+        if trace {
+            println!("{:4} {:<10} {}", pc, fmt_cmd(&prog[pc]), m.regs);
+        }
+    }
+    println!("final: {}", m.regs);
+}
+
+// The 18-instruction block MONAD repeats once per digit; `?` marks the
+// per-block parameters (div, add_x, add_y).
+const MONAD_BLOCK: [&str; 18] = [
+    "inp w", "mul x 0", "add x z", "mod x 26", "div z ?", "add x ?", "eql x w", "eql x 0",
+    "mul y 0", "add y 25", "mul y x", "add y 1", "mul z y", "mul y 0", "add y w", "add y ?",
+    "mul y x", "add z y",
+];
+
+fn monad_params(block: &[Cmd]) -> Option<(i64, i64, i64)> {
+    if block.len() != MONAD_BLOCK.len() {
+        return None;
+    }
+    let mut params = vec![];
+    for (c, pat) in block.iter().zip(MONAD_BLOCK.iter()) {
+        let s = fmt_cmd(c);
+        match pat.strip_suffix('?') {
+            Some(prefix) => params.push(s.strip_prefix(prefix)?.parse().ok()?),
+            None if s == *pat => {}
+            None => return None,
+        }
+    }
+    Some((params[0], params[1], params[2]))
+}
+
+// Splits the program at each `inp` and prints the MONAD parameters of every
+// block. Blocks with `div z 1` push `in + add_y` onto the base-26 stack in z;
+// `div z 26` blocks pop and require `in == top + add_x`.
+fn disassemble(prog: &[Cmd]) {
+    let mut starts: Vec<usize> = (0..prog.len())
+        .filter(|&i| matches!(prog[i], Cmd::Inp(_)))
+        .collect();
+    starts.push(prog.len());
+    if starts[0] > 0 {
+        println!("preamble:");
+        prog[..starts[0]]
+            .iter()
+            .for_each(|c| println!("    {}", fmt_cmd(c)));
+    }
+    println!("block  div  add_x  add_y");
+    for (k, w) in starts.windows(2).enumerate() {
+        let block = &prog[w[0]..w[1]];
+        match monad_params(block) {
+            Some((div, add_x, add_y)) => {
+                let kind = match div {
+                    1 => "push",
+                    26 => "pop",
+                    _ => "",
+                };
+                println!("{:5} {:4} {:6} {:6}  {}", k, div, add_x, add_y, kind);
+            }
+            None => {
+                println!("{:5}  (not a MONAD block)", k);
+                block.iter().for_each(|c| println!("    {}", fmt_cmd(c)));
+            }
+        }
+    }
+}
+
+fn parse_digits(s: &str) -> Vec<i64> {
+    s.chars()
+        .map(|c| c.to_digit(10).expect("input must be digits") as i64)
+        .collect()
+}
+
+// Closed integer range, used to over-approximate register values.
+type Range = (i64, i64);
+
+fn range_op(op: Op, (a1, a2): Range, (b1, b2): Range) -> Option<Range> {
+    let hull = |v: &[i64]| (*v.iter().min().unwrap(), *v.iter().max().unwrap());
+    match op {
+        Op::Add => Some((a1.saturating_add(b1), a2.saturating_add(b2))),
+        Op::Mul => Some(hull(&[
+            a1.saturating_mul(b1),
+            a1.saturating_mul(b2),
+            a2.saturating_mul(b1),
+            a2.saturating_mul(b2),
+        ])),
+        Op::Div => {
+            // Division by zero never succeeds, so split the divisor around it.
+            let mut ends = vec![];
+            for (lo, hi) in [(b1, b2.min(-1)), (b1.max(1), b2)] {
+                if lo <= hi {
+                    ends.extend([
+                        a1.saturating_div(lo),
+                        a1.saturating_div(hi),
+                        a2.saturating_div(lo),
+                        a2.saturating_div(hi),
+                    ]);
+                }
+            }
+            if ends.is_empty() {
+                None
+            } else {
+                Some(hull(&ends))
+            }
+        }
+        Op::Mod => {
+            let (a1, b1) = (a1.max(0), b1.max(1));
+            if a1 > a2 || b1 > b2 {
+                None
+            } else if a2 < b1 {
+                Some((a1, a2))
+            } else {
+                Some((0, a2.min(b2 - 1)))
+            }
+        }
+        Op::Eql => Some(if a1 == a2 && b1 == b2 && a1 == b1 {
+            (1, 1)
+        } else if a2 < b1 || b2 < a1 {
+            (0, 0)
+        } else {
+            (0, 1)
+        }),
+    }
+}
+
+// Interval evaluation of `prog` from the given registers with every input
+// in 1..=9. Returns false only if z == 0 at the end is impossible.
+fn may_accept(prog: &[Cmd], regs: &Regs) -> bool {
+    let mut r: [Range; 4] = regs.0.map(|v| (v, v));
+    for c in prog {
+        match c {
+            Cmd::Inp(v) => r[v.idx()] = (1, 9),
+            Cmd::Cmd(op, v, val) => {
+                let b = match val {
+                    Val::L(n) => (*n, *n),
+                    Val::V(u) => r[u.idx()],
+                };
+                match range_op(*op, r[v.idx()], b) {
+                    Some(x) => r[v.idx()] = x,
+                    None => return false,
+                }
+            }
+        }
+    }
+    let (lo, hi) = r[Var::Z.idx()];
+    lo <= 0 && 0 <= hi
+}
+
+// Registers read before being written in `prog`, i.e. the ones whose value
+// on entry can influence the final z or whether the run faults. As in
+// `compile`, an instruction that may fail keeps its operands live even when
+// its result is never used.
+fn live_in(prog: &[Cmd]) -> [bool; 4] {
+    let mut live = [false, false, false, true];
+    for c in prog.iter().rev() {
+        match c {
+            Cmd::Inp(v) => live[v.idx()] = false,
+            Cmd::Cmd(op, v, val) => {
+                // `mul v 0` overwrites v without depending on it.
+                let kills = matches!((op, val), (Op::Mul, Val::L(0)));
+                let k = match val {
+                    Val::L(n) => Some(*n),
+                    Val::V(_) => None,
+                };
+                if !kills && !live[v.idx()] && op_is_safe(*op, k) {
+                    continue;
+                }
+                live[v.idx()] = !kills;
+                if let Val::V(u) = val {
+                    live[u.idx()] = true;
+                }
+            }
+        }
+    }
+    live
+}
+
+// Depth-first search for the largest (or smallest) digit sequence accepted
+// by an arbitrary program. States that failed before are memoised on
+// (input index, live registers), so MONAD-like programs collapse to
+// (step, z); interval evaluation prunes states that can never reach z == 0.
+struct Searcher<'a> {
+    // segs[k] starts at the k-th `inp` (segs[0] is any preamble before it).
+    segs: Vec<&'a [Cmd]>,
+    rest: Vec<&'a [Cmd]>,
+    live: Vec<[bool; 4]>,
+    // Candidate digits, in search order, for the input read by segs[k].
+    digits: Vec<Vec<i64>>,
+    dead: HashSet<(usize, Regs)>,
+    counts: HashMap<(usize, Regs), u64>,
+    expanded: usize,
+}
+
+impl<'a> Searcher<'a> {
+    // `fixed` pins input digits, as from `parse_pattern`.
+    fn new(prog: &'a [Cmd], largest: bool, fixed: &[Option<i64>]) -> Self {
+        let mut starts: Vec<usize> = prog
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c, Cmd::Inp(_)))
+            .map(|(i, _)| i)
+            .collect();
+        starts.insert(0, 0);
+        starts.push(prog.len());
+        let segs = starts.windows(2).map(|w| &prog[w[0]..w[1]]).collect();
+        let rest: Vec<&[Cmd]> = starts.iter().map(|&i| &prog[i..]).collect();
+        let live = rest.iter().map(|r| live_in(r)).collect();
+        let order: Vec<i64> = if largest {
+            (1..=9).rev().collect()
+        } else {
+            (1..=9).collect()
+        };
+        let digits = (0..starts.len() - 1)
+            .map(|k| match k.checked_sub(1).and_then(|i| fixed.get(i)) {
+                Some(&Some(d)) => vec![d],
+                _ => order.clone(),
+            })
+            .collect();
+        Searcher {
+            segs,
+            rest,
+            live,
+            digits,
+            dead: HashSet::new(),
+            counts: HashMap::new(),
+            expanded: 0,
+        }
+    }
+
+    fn key(&self, k: usize, regs: &Regs) -> (usize, Regs) {
+        let mut r = *regs;
+        for (i, live) in self.live[k].iter().enumerate() {
+            if !live {
+                r.0[i] = 0;
+            }
+        }
+        (k, r)
+    }
+
+    // Runs segment k (whose first instruction consumes `digit`, if any).
+    fn exec(&self, k: usize, regs: &Regs, digit: i64) -> Result<Regs, ExecError> {
+        let input = [digit];
+        let mut m = Machine::new(self.segs[k], &input);
+        m.regs = *regs;
+        m.run()
+    }
+
+    fn dfs(&mut self, k: usize, regs: &Regs, input: &mut Vec<i64>) -> bool {
+        if k == self.segs.len() {
+            return regs.get(Var::Z) == 0;
+        }
+        let key = self.key(k, regs);
+        if self.dead.contains(&key) || !may_accept(self.rest[k], regs) {
+            return false;
+        }
+        self.expanded += 1;
+        for i in 0..self.digits[k].len() {
+            let digit = self.digits[k][i];
+            if let Ok(next) = self.exec(k, regs, digit) {
+                input.push(digit);
+                if self.dfs(k + 1, &next, input) {
+                    return true;
+                }
+                input.pop();
+            }
+        }
+        self.dead.insert(key);
+        false
+    }
+
+    // Like `dfs` but calls `f` on every accepted input, in search order,
+    // until it returns false. Returns None once stopped, else whether
+    // anything was accepted below this state.
+    fn visit(
+        &mut self,
+        k: usize,
+        regs: &Regs,
+        input: &mut Vec<i64>,
+        f: &mut dyn FnMut(&[i64]) -> bool,
+    ) -> Option<bool> {
+        if k == self.segs.len() {
+            let ok = regs.get(Var::Z) == 0;
+            return if ok && !f(input) { None } else { Some(ok) };
+        }
+        let key = self.key(k, regs);
+        if self.dead.contains(&key) || !may_accept(self.rest[k], regs) {
+            return Some(false);
+        }
+        self.expanded += 1;
+        let mut found = false;
+        for i in 0..self.digits[k].len() {
+            let digit = self.digits[k][i];
+            if let Ok(next) = self.exec(k, regs, digit) {
+                input.push(digit);
+                let r = self.visit(k + 1, &next, input, f);
+                input.pop();
+                found |= r?;
+            }
+        }
+        if !found {
+            self.dead.insert(key);
+        }
+        Some(found)
+    }
+
+    // Number of accepted inputs reachable from this state.
+    fn count(&mut self, k: usize, regs: &Regs) -> u64 {
+        if k == self.segs.len() {
+            return (regs.get(Var::Z) == 0) as u64;
+        }
+        let key = self.key(k, regs);
+        if let Some(&n) = self.counts.get(&key) {
+            return n;
+        }
+        let mut n = 0;
+        if may_accept(self.rest[k], regs) {
+            self.expanded += 1;
+            for i in 0..self.digits[k].len() {
+                if let Ok(next) = self.exec(k, regs, self.digits[k][i]) {
+                    n += self.count(k + 1, &next);
+                }
+            }
+        }
+        self.counts.insert(key, n);
+        n
+    }
+}
+
+// Input digits pinned by a pattern such as "99" (prefix) or "??7" (third
+// digit is 7); '?' or '.' and positions past the end of the pattern are
+// free (None). The symbolic solvers pin these digits in `solve_it`'s input,
+// where the Term::E constraints see them; `search`, `--all` and `--count`
+// bypass Term::E and just restrict the digits tried at each input.
+fn parse_pattern(p: &str) -> Vec<Option<i64>> {
+    p.chars()
+        .map(|c| match c {
+            '?' | '.' => None,
+            '1'..='9' => Some(parse_digits(&c.to_string())[0]),
+            _ => panic!("pattern digits must be 1-9, or ? or . for any digit"),
+        })
+        .collect()
+}
+
+// Calls `f` on every accepted input in increasing order until it returns
+// false.
+fn for_each_valid(prog: &[Cmd], fixed: &[Option<i64>], f: &mut dyn FnMut(&[i64]) -> bool) {
+    let mut s = Searcher::new(prog, false, fixed);
+    if let Ok(regs) = s.exec(0, &Regs::default(), 0) {
+        s.visit(1, &regs, &mut vec![], f);
+    }
+}
+
+fn count_valid(prog: &[Cmd], fixed: &[Option<i64>]) -> u64 {
+    let mut s = Searcher::new(prog, false, fixed);
+    s.exec(0, &Regs::default(), 0)
+        .map_or(0, |regs| s.count(1, &regs))
+}
+
+fn to_number(digits: &[i64]) -> i64 {
+    digits.iter().fold(0, |a, i| a * 10 + i)
+}
+
+fn search(prog: &[Cmd], largest: bool, fixed: &[Option<i64>], stats: bool) -> Option<Vec<i64>> {
+    let mut s = Searcher::new(prog, largest, fixed);
+    // The preamble consumes no input.
+    let regs = s.exec(0, &Regs::default(), 0).ok()?;
+    let mut input = vec![];
+    let found = s.dfs(1, &regs, &mut input);
+    if stats {
+        eprintln!("search: expanded {} states", s.expanded);
+    }
+    if found {
+        Some(input)
+    } else {
+        None
+    }
+}
+
 fn next_dec(v: &mut Vec<i64>) -> bool {
     let mut n = v.len() - 1;
     loop {
@@ -582,7 +1374,7 @@ fn solve_it(
     }
 }
 
-fn solve1(h: &Vec<Cmd>) -> i64 {
+fn solve1(h: &Vec<Cmd>, fixed: &[Option<i64>]) -> Option<i64> {
     let mut alu = ALU::new();
     // Symbolic evaluation and simple simplifications:
     h.iter().enumerate().for_each(|(_i, s)| match s {
@@ -617,9 +1409,10 @@ fn solve1(h: &Vec<Cmd>) -> i64 {
         }
         valid += 1;
 
-        // This just appears to be the case.
+        // This just appears to be the case for MONAD; anything else is left
+        // to the general `search`.
         if min != max {
-            panic!("oops!");
+            return None;
         }
 
         // println!("MinMax: {:?}", s.min_max());
@@ -635,17 +1428,22 @@ fn solve1(h: &Vec<Cmd>) -> i64 {
 
         // Now try each possible value of the inputs till we get some values
         // satisfying the Term::E(i) constraints.
-        let mut input = vec![0; 14];
+        let mut input = vec![0; alu.i_count];
+        // solve_it treats 0 as a digit still to choose.
+        input
+            .iter_mut()
+            .zip(fixed)
+            .for_each(|(i, &d)| *i = d.unwrap_or(0));
         let rs = solve_it(&de, &de_exp, &mut input, &alu.z_term, &s, 0, false);
         if rs {
-            return input.iter().fold(0, |a, i| a * 10 + i);
+            return Some(input.iter().fold(0, |a, i| a * 10 + i));
         }
     }
     // println!("Valid: {}", valid);
-    0
+    None
 }
 
-fn solve2(h: &Vec<Cmd>) -> i64 {
+fn solve2(h: &Vec<Cmd>, fixed: &[Option<i64>]) -> Option<i64> {
     let mut alu = ALU::new();
     h.iter().enumerate().for_each(|(_i, s)| match s {
         Cmd::Inp(v) => alu.inp(*v),
@@ -674,7 +1472,7 @@ fn solve2(h: &Vec<Cmd>) -> i64 {
         }
         valid += 1;
         if min != max {
-            panic!("oops!");
+            return None;
         }
 
         // println!("MinMax: {:?}", s.min_max());
@@ -697,22 +1495,220 @@ fn solve2(h: &Vec<Cmd>) -> i64 {
             .iter()
             .for_each(|exp| de_exp.push(exp.eval(&vec![], &de)));
 
-        let mut input = vec![0; 14];
+        let mut input = vec![0; alu.i_count];
+        // solve_it treats 0 as a digit still to choose.
+        input
+            .iter_mut()
+            .zip(fixed)
+            .for_each(|(i, &d)| *i = d.unwrap_or(0));
         let rs = solve_it(&de, &de_exp, &mut input, &alu.z_term, &s, 0, true);
         if rs {
-            return input.iter().fold(0, |a, i| a * 10 + i);
+            return Some(input.iter().fold(0, |a, i| a * 10 + i));
         }
     }
     // println!("Valid: {}", valid);
 
-    0
+    None
 }
 
 fn main() -> Result<(), io::Error> {
     let stdin = io::stdin();
     let v: Vec<String> = stdin.lock().lines().collect::<Result<Vec<String>, _>>()?;
     let lines = parse_input(&v);
-    println!("{}", solve1(&lines));
-    println!("{}", solve2(&lines));
+
+    // --run DIGITS [--trace] [--break PC,PC...]: execute concretely.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |f: &str| {
+        args.iter()
+            .position(|a| a == f)
+            .and_then(|i| args.get(i + 1))
+    };
+    if let Some(digits) = arg("--run") {
+        let breakpoints = arg("--break")
+            .map(|b| b.split(',').map(|x| x.parse().unwrap()).collect())
+            .unwrap_or_default();
+        let trace = args.iter().any(|a| a == "--trace");
+        debug(&lines, &parse_digits(digits), trace, &breakpoints);
+        return Ok(());
+    }
+
+    // --check-compile N: compare compiled code with the interpreter.
+    if let Some(n) = arg("--check-compile") {
+        check_compile(&lines, n.parse().unwrap());
+        return Ok(());
+    }
+
+    // --disasm: MONAD block parameters; --explain: symbolic registers.
+    if args.iter().any(|a| a == "--disasm") {
+        disassemble(&lines);
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--explain") {
+        let mut alu = ALU::new();
+        lines.iter().for_each(|s| match s {
+            Cmd::Inp(v) => alu.inp(*v),
+            Cmd::Cmd(op, var, arg) => alu.op(*op, *var, *arg),
+        });
+        alu.print();
+        return Ok(());
+    }
+
+    // --pattern P: constrain digits, e.g. "99" or "??7" ('?' or '.' is any
+    // digit); --all [--limit N] lists every accepted input in increasing
+    // order; --count counts them.
+    let fixed = arg("--pattern").map_or(vec![], |p| parse_pattern(p));
+    if args.iter().any(|a| a == "--all") {
+        let mut limit = arg("--limit").map_or(usize::MAX, |n| n.parse().unwrap());
+        if limit > 0 {
+            for_each_valid(&lines, &fixed, &mut |v| {
+                println!("{}", to_number(v));
+                limit -= 1;
+                limit > 0
+            });
+        }
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--count") {
+        println!("{}", count_valid(&lines, &fixed));
+        return Ok(());
+    }
+
+    // --symbolic: try the Term::E based solver first.
+    let symbolic = args.iter().any(|a| a == "--symbolic");
+    let stats = args.iter().any(|a| a == "--stats");
+    for largest in [true, false] {
+        let sym = match (symbolic, largest) {
+            (false, _) => None,
+            (true, true) => solve1(&lines, &fixed),
+            (true, false) => solve2(&lines, &fixed),
+        };
+        let n = match sym.or_else(|| search(&lines, largest, &fixed, stats).map(|v| to_number(&v)))
+        {
+            Some(n) => n,
+            None => {
+                println!("no valid input");
+                continue;
+            }
+        };
+        println!("{}", n);
+        match run(&lines, &parse_digits(&n.to_string())) {
+            Ok(r) if r.get(Var::Z) == 0 => {}
+            Ok(r) => println!("  rejected by ALU: {}", r),
+            Err(e) => println!("  rejected by ALU: {}", e),
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Computes `w / x` into a dead register, but x is 0 (a fault) exactly
+    // when the first digit is 9.
+    const FAULTS_ON_NINE: &str = "inp w
+add y w
+mod y 9
+inp w
+mul x 0
+add x w
+mul x y
+add x w
+mul z 0
+add z w
+mul z -1
+add x z
+mul z 0
+div w x";
+
+    fn program(src: &str) -> Vec<Cmd> {
+        parse_input(&src.lines().map(String::from).collect())
+    }
+
+    // A random straight-line program. Division or modulus by a register
+    // can fault, so both the results and the errors get compared.
+    fn random_program(rng: &mut Rng, len: usize) -> String {
+        let regs = ["w", "x", "y", "z"];
+        let ops = ["add", "mul", "div", "mod", "eql"];
+        let mut src = vec!["inp w".to_string()];
+        for _ in 0..len {
+            let r = regs[rng.below(4)];
+            if rng.below(6) == 0 {
+                src.push(format!("inp {}", r));
+                continue;
+            }
+            let op = ops[rng.below(5)];
+            let arg = if op == "div" || op == "mod" {
+                match rng.below(8) {
+                    0 => regs[rng.below(4)].to_string(),
+                    _ => rng.range(1, 26).to_string(),
+                }
+            } else {
+                match rng.below(3) {
+                    0 => regs[rng.below(4)].to_string(),
+                    _ => rng.range(-3, 26).to_string(),
+                }
+            };
+            src.push(format!("{} {} {}", op, r, arg));
+        }
+        src.join("\n")
+    }
+
+    fn assert_compiled_agrees(prog: &[Cmd], rng: &mut Rng, trials: usize) {
+        let full = compile(prog, [true; 4]);
+        let z_only = compile(prog, [false, false, false, true]);
+        let inputs = prog.iter().filter(|c| matches!(c, Cmd::Inp(_))).count();
+        for _ in 0..trials {
+            let input: Vec<i64> = (0..inputs).map(|_| rng.range(1, 9)).collect();
+            assert!(
+                compiled_agrees(prog, &full, &z_only, &input),
+                "{:?} on {:?}",
+                prog,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        let mut rng = Rng::new(31);
+        assert_compiled_agrees(&program(include_str!("../input")), &mut rng, 200);
+        assert_compiled_agrees(&program(FAULTS_ON_NINE), &mut rng, 50);
+        for _ in 0..300 {
+            let src = random_program(&mut rng, 30);
+            assert_compiled_agrees(&program(&src), &mut rng, 20);
+        }
+    }
+
+    #[test]
+    fn pattern_wildcards() {
+        assert_eq!(parse_pattern("9?.7"), vec![Some(9), None, None, Some(7)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "pattern digits must be 1-9")]
+    fn pattern_rejects_zero() {
+        parse_pattern("90");
+    }
+
+    #[test]
+    fn search_keeps_fallible_operands_live() {
+        let prog = program(FAULTS_ON_NINE);
+        assert_eq!(search(&prog, true, &[], false), Some(vec![8, 9]));
+        assert_eq!(search(&prog, false, &[], false), Some(vec![1, 1]));
+    }
+
+    #[test]
+    fn count_matches_listing() {
+        let prog = program(FAULTS_ON_NINE);
+        let mut all = vec![];
+        for_each_valid(&prog, &[], &mut |v| {
+            all.push(to_number(v));
+            true
+        });
+        assert_eq!(all.len(), 72);
+        assert!(all.iter().all(|n| n / 10 != 9));
+        assert_eq!(count_valid(&prog, &[]), 72);
+        assert_eq!(count_valid(&prog, &[Some(9)]), 0);
+    }
+}
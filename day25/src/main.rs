@@ -1,73 +1,18 @@
+use automaton::{Automaton, Boundary, Grid, Herds};
 use std::io::{self, BufRead};
 
 fn parse_input(lines: &Vec<String>) -> Vec<Vec<char>> {
     lines.iter().map(|x| x.chars().collect()).collect()
 }
 
-fn print(h: &Vec<Vec<char>>) {
-    h.iter()
-        .map(|x| x.iter().collect::<String>())
-        .for_each(|x| println!("{}", x));
-}
-
 fn solve1(h: &Vec<Vec<char>>) -> i64 {
-    let mut p = h.clone();
-    let mut c = h.clone();
-    let mut steps = 0;
-    let rows = p.len();
-    let cols = p[0].len();
-    loop {
-        // Clear c.
-        for i in 0..rows {
-            for j in 0..cols {
-                c[i][j] = '.';
-            }
-        }
-
-        let mut moved_count = 0;
-
-        // Check east facing
-        for i in 0..rows {
-            for j in 0..cols {
-                // c[i][j] = '.';
-                match p[i][j] {
-                    '>' => {
-                        if p[i][(j + 1) % cols] == '.' {
-                            c[i][(j + 1) % cols] = '>';
-                            moved_count += 1;
-                        } else {
-                            c[i][j] = '>';
-                        }
-                    }
-                    'v' => {
-                        if (p[(i + 1) % rows][j] == '.'
-                            && p[(i + 1) % rows][(j + cols - 1) % cols] != '>')
-                            || (p[(i + 1) % rows][j] == '>'
-                                && p[(i + 1) % rows][(j + 1) % cols] == '.')
-                        {
-                            c[(i + 1) % rows][j] = 'v';
-                            moved_count += 1;
-                        } else {
-                            c[i][j] = 'v';
-                        }
-                    }
-                    '.' => {}
-                    _ => panic!("should not happen: {}", p[i][j]),
-                }
-            }
-        }
-
-        if moved_count == 0 {
-            break steps + 1;
-        }
-        steps += 1;
-        let tmp = p;
-        p = c;
-        c = tmp;
-
-        // println!("Step {}:", steps);
-        // print(&p);
-    }
+    let grid = Grid::new(h.clone(), Boundary::Toroidal, '.');
+    let herds = Herds {
+        empty: '.',
+        herds: vec![('>', (0, 1)), ('v', (1, 0))],
+    };
+    let mut a = Automaton::new(grid, herds);
+    a.run_until_stable(usize::MAX).unwrap() as i64
 }
 
 fn solve2(h: &Vec<Vec<char>>) -> i64 {
@@ -0,0 +1,390 @@
+//! Shortest paths over implicit graphs, shared by the grid and state-space
+//! days.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// A graph given by its edges out of each state.
+pub trait Graph {
+    type State: Clone + Eq + Hash;
+
+    /// Every state reachable in one step, with the (non-negative) cost.
+    fn neighbors(&self, s: &Self::State) -> impl Iterator<Item = (Self::State, i64)>;
+}
+
+/// Where a search keeps each state's best distance and predecessor.
+pub trait Table<S> {
+    fn dist(&self, s: &S) -> Option<i64>;
+    fn prev(&self, s: &S) -> Option<S>;
+    /// Records `d` as the distance to `s`, reached from `from` (None for
+    /// the start).
+    fn set(&mut self, s: &S, d: i64, from: Option<&S>);
+}
+
+/// Hash maps, for any state space.
+pub struct Hashed<S> {
+    dist: HashMap<S, i64>,
+    prev: HashMap<S, S>,
+}
+
+impl<S> Default for Hashed<S> {
+    fn default() -> Self {
+        Hashed {
+            dist: HashMap::new(),
+            prev: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Clone + Eq + Hash> Table<S> for Hashed<S> {
+    fn dist(&self, s: &S) -> Option<i64> {
+        self.dist.get(s).copied()
+    }
+
+    fn prev(&self, s: &S) -> Option<S> {
+        self.prev.get(s).cloned()
+    }
+
+    fn set(&mut self, s: &S, d: i64, from: Option<&S>) {
+        self.dist.insert(s.clone(), d);
+        if let Some(f) = from {
+            self.prev.insert(s.clone(), f.clone());
+        }
+    }
+}
+
+/// Flat arrays, for states numbered 0..n such as the cells of a grid.
+pub struct Dense {
+    dist: Vec<i64>,
+    prev: Vec<usize>,
+}
+
+// Marks unset entries in `Dense`.
+const NONE: usize = usize::MAX;
+
+impl Dense {
+    pub fn new(n: usize) -> Self {
+        Dense {
+            dist: vec![i64::MAX; n],
+            prev: vec![NONE; n],
+        }
+    }
+}
+
+impl Table<usize> for Dense {
+    fn dist(&self, &s: &usize) -> Option<i64> {
+        Some(self.dist[s]).filter(|&d| d != i64::MAX)
+    }
+
+    fn prev(&self, &s: &usize) -> Option<usize> {
+        Some(self.prev[s]).filter(|&p| p != NONE)
+    }
+
+    fn set(&mut self, &s: &usize, d: i64, from: Option<&usize>) {
+        self.dist[s] = d;
+        if let Some(&f) = from {
+            self.prev[s] = f;
+        }
+    }
+}
+
+/// Result of a search: best known cost to every state seen, and how each
+/// was reached.
+pub struct Search<S, T = Hashed<S>> {
+    table: T,
+    /// The goal state reached, if any.
+    pub goal: Option<S>,
+    /// Number of states taken off the queue and expanded.
+    pub expanded: usize,
+}
+
+impl<S: Clone, T: Table<S>> Search<S, T> {
+    pub fn cost(&self) -> Option<i64> {
+        self.dist(self.goal.as_ref()?)
+    }
+
+    /// Best known distance to `s`; exact for every state once a search
+    /// with no goal has finished.
+    pub fn dist(&self, s: &S) -> Option<i64> {
+        self.table.dist(s)
+    }
+
+    /// States from the start to the goal.
+    pub fn path(&self) -> Option<Vec<S>> {
+        let mut node = self.goal.clone()?;
+        let mut path = vec![node.clone()];
+        while let Some(p) = self.table.prev(&node) {
+            path.push(p.clone());
+            node = p;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn new(mut table: T, start: &S) -> Self {
+        table.set(start, 0, None);
+        Search {
+            table,
+            goal: None,
+            expanded: 0,
+        }
+    }
+
+    // Records a route to `to` through `from` if it is the best so far.
+    fn relax(&mut self, from: &S, to: &S, d: i64) -> bool {
+        if self.dist(to).is_some_and(|curr| curr <= d) {
+            return false;
+        }
+        self.table.set(to, d, Some(from));
+        true
+    }
+}
+
+// Queue entry ordered by priority alone, so states need no `Ord`.
+struct Entry<S> {
+    f: i64,
+    d: i64,
+    state: S,
+}
+
+impl<S> PartialEq for Entry<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.f == other.f
+    }
+}
+
+impl<S> Eq for Entry<S> {}
+
+impl<S> Ord for Entry<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f.cmp(&self.f)
+    }
+}
+
+impl<S> PartialOrd for Entry<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* from `start` until `is_goal`. `h` must never overestimate the
+/// remaining cost for the result to be optimal; an inconsistent `h` only
+/// costs re-expansions.
+pub fn astar<G: Graph>(
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+    h: impl Fn(&G::State) -> i64,
+) -> Search<G::State> {
+    astar_in(Hashed::default(), g, start, is_goal, h)
+}
+
+/// `astar` keeping its bookkeeping in `table`.
+pub fn astar_in<G: Graph, T: Table<G::State>>(
+    table: T,
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+    h: impl Fn(&G::State) -> i64,
+) -> Search<G::State, T> {
+    let mut s = Search::new(table, &start);
+    // Min-heap on f (Entry reverses the comparison).
+    let mut heap = BinaryHeap::new();
+    heap.push(Entry {
+        f: h(&start),
+        d: 0,
+        state: start,
+    });
+    while let Some(Entry { d, state: node, .. }) = heap.pop() {
+        if s.dist(&node).unwrap() < d {
+            // A better route was found after this entry was queued.
+            continue;
+        }
+        if is_goal(&node) {
+            s.goal = Some(node);
+            break;
+        }
+        s.expanded += 1;
+        for (nbr, cost) in g.neighbors(&node) {
+            let nd = d + cost;
+            if s.relax(&node, &nbr, nd) {
+                heap.push(Entry {
+                    f: nd + h(&nbr),
+                    d: nd,
+                    state: nbr,
+                });
+            }
+        }
+    }
+    s
+}
+
+/// Dijkstra's algorithm: A* without a heuristic.
+pub fn dijkstra<G: Graph>(
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+) -> Search<G::State> {
+    astar(g, start, is_goal, |_| 0)
+}
+
+/// `dijkstra` keeping its bookkeeping in `table`.
+pub fn dijkstra_in<G: Graph, T: Table<G::State>>(
+    table: T,
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+) -> Search<G::State, T> {
+    astar_in(table, g, start, is_goal, |_| 0)
+}
+
+/// Dijkstra with a bucket queue (Dial's algorithm), for edge costs in
+/// 0..=max_cost. With max_cost = 1 this is 0-1 BFS.
+pub fn buckets<G: Graph>(
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+    max_cost: i64,
+) -> Search<G::State> {
+    buckets_in(Hashed::default(), g, start, is_goal, max_cost)
+}
+
+/// `buckets` keeping its bookkeeping in `table`.
+pub fn buckets_in<G: Graph, T: Table<G::State>>(
+    table: T,
+    g: &G,
+    start: G::State,
+    is_goal: impl Fn(&G::State) -> bool,
+    max_cost: i64,
+) -> Search<G::State, T> {
+    let mut s = Search::new(table, &start);
+    // Every queued distance is within max_cost of the current one, so a
+    // ring of max_cost + 1 buckets suffices.
+    let n = max_cost as usize + 1;
+    let mut ring: Vec<Vec<G::State>> = vec![vec![]; n];
+    let mut queued = 1;
+    ring[0].push(start);
+    let mut d = 0;
+    while queued > 0 {
+        let Some(node) = ring[d as usize % n].pop() else {
+            d += 1;
+            continue;
+        };
+        queued -= 1;
+        if s.dist(&node).unwrap() < d {
+            continue;
+        }
+        if is_goal(&node) {
+            s.goal = Some(node);
+            break;
+        }
+        s.expanded += 1;
+        for (nbr, cost) in g.neighbors(&node) {
+            assert!(
+                (0..=max_cost).contains(&cost),
+                "edge cost {} out of range",
+                cost
+            );
+            let nd = d + cost;
+            if s.relax(&node, &nbr, nd) {
+                ring[nd as usize % n].push(nbr);
+                queued += 1;
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Directed graph over node indices.
+    struct Edges(Vec<Vec<(usize, i64)>>);
+
+    impl Graph for Edges {
+        type State = usize;
+
+        fn neighbors(&self, s: &usize) -> impl Iterator<Item = (usize, i64)> {
+            self.0[*s].iter().copied()
+        }
+    }
+
+    fn path_cost(g: &Edges, path: &[usize]) -> i64 {
+        path.windows(2)
+            .map(|w| g.0[w[0]].iter().find(|e| e.0 == w[1]).unwrap().1)
+            .sum()
+    }
+
+    #[test]
+    fn searches_agree() {
+        // 0 -> 3 costs 7 directly, 5 via 1 and 2.
+        let g = Edges(vec![
+            vec![(1, 2), (3, 7), (4, 1)],
+            vec![(2, 1)],
+            vec![(3, 2)],
+            vec![],
+            vec![(4, 0)],
+        ]);
+        let goal = |s: &usize| *s == 3;
+        for r in [
+            dijkstra(&g, 0, goal),
+            astar(&g, 0, goal, |&s| (s != 3) as i64),
+            buckets(&g, 0, goal, 7),
+        ] {
+            assert_eq!(r.cost(), Some(5));
+            assert_eq!(r.path(), Some(vec![0, 1, 2, 3]));
+        }
+    }
+
+    #[test]
+    fn zero_cost_edges_in_buckets() {
+        // 0-1 BFS: the free chain 0 -> 1 -> 2 -> 3 beats the direct edge.
+        let g = Edges(vec![
+            vec![(3, 1), (1, 0)],
+            vec![(2, 0)],
+            vec![(3, 0)],
+            vec![],
+        ]);
+        let r = buckets(&g, 0, |s| *s == 3, 1);
+        assert_eq!(r.cost(), Some(0));
+        assert_eq!(r.path(), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn path_after_reopening() {
+        // h(2) = 10 is admissible but inconsistent, so 1 is expanded via the
+        // direct edge first and re-opened once 0 -> 2 -> 1 is found.
+        let g = Edges(vec![
+            vec![(1, 4), (2, 1)],
+            vec![(3, 10)],
+            vec![(1, 1)],
+            vec![],
+        ]);
+        let r = astar(&g, 0, |s| *s == 3, |&s| if s == 2 { 10 } else { 0 });
+        assert_eq!(r.cost(), Some(12));
+        let path = r.path().unwrap();
+        assert_eq!(path, vec![0, 2, 1, 3]);
+        assert_eq!(path_cost(&g, &path), 12);
+        assert_eq!(r.expanded, 4);
+    }
+
+    #[test]
+    fn dense_table_matches_hashed() {
+        let g = Edges(vec![
+            vec![(1, 4), (2, 1)],
+            vec![(3, 10)],
+            vec![(1, 1)],
+            vec![],
+        ]);
+        let dense = buckets_in(Dense::new(4), &g, 0, |_| false, 10);
+        let hashed = buckets(&g, 0, |_| false, 10);
+        for v in 0..4 {
+            assert_eq!(dense.dist(&v), hashed.dist(&v));
+        }
+        let r = dijkstra_in(Dense::new(4), &g, 0, |s| *s == 3);
+        assert_eq!(r.cost(), Some(12));
+        assert_eq!(r.path(), Some(vec![0, 2, 1, 3]));
+    }
+}